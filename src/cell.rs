@@ -1,4 +1,4 @@
-use std::ops::{Add, BitOrAssign};
+use std::ops::{Add, BitOr, BitOrAssign};
 use crate::{CLEAR, Color, WHITE};
 
 /// A cell's contents.
@@ -10,6 +10,10 @@ pub struct Cell {
     pub fg: Color,
     /// the background, used for black pixels in the bitmap
     pub bg: Color,
+    /// Whether the cell blinks, see `Layer::draw_at_phase`
+    pub blink: bool,
+    /// Extra styling applied when the cell is drawn, see `Style`
+    pub style: Style,
 }
 
 impl Default for Cell {
@@ -18,6 +22,65 @@ impl Default for Cell {
     }
 }
 
+/// The inverse of applying a component with `|=`: these reset one field of a cell back to what
+/// `Cell::default()` has, leaving the rest alone.
+/// ```
+/// # use heart437::*;
+/// let mut cell = Fg(RED) + Bg(BLUE) + Char(b'#');
+/// cell.clear_fg();
+/// cell.clear_bg();
+/// let mut expected = Cell::default();
+/// expected |= Char(b'#');
+/// assert_eq!(cell, expected);
+/// ```
+impl Cell {
+    /// Reset the foreground to the default, white
+    pub fn clear_fg(&mut self) {
+        self.fg = Cell::default().fg
+    }
+
+    /// Reset the background to the default, clear
+    pub fn clear_bg(&mut self) {
+        self.bg = Cell::default().bg
+    }
+
+    /// Reset the character to the default, a space
+    pub fn clear_char(&mut self) {
+        self.ch = Cell::default().ch
+    }
+
+    /// A copy of this cell with its foreground and background swapped, for reverse video.
+    /// This is permanent, unlike `Style::REVERSE`, which only swaps them when drawing.
+    /// ```
+    /// # use heart437::*;
+    /// let item = Fg(WHITE) + Bg(BLUE) + Char(b'>');
+    /// assert_eq!(item.reversed(), Fg(BLUE) + Bg(WHITE) + Char(b'>'));
+    /// ```
+    pub fn reversed(&self) -> Cell {
+        Cell { fg: self.bg, bg: self.fg, ..*self }
+    }
+
+    /// Pack this cell into a stable 9-byte layout: the char, then the RGBA of the fg, then the
+    /// RGBA of the bg. `blink` and `style` aren't included. This is also the per-cell layout of
+    /// `Layer::serialize`.
+    /// ```
+    /// # use heart437::*;
+    /// let cell = Fg(RED) + Bg(BLUE) + Char(b'@');
+    /// assert_eq!(cell.to_bytes(), [b'@', 255, 0, 0, 255, 0, 0, 255, 255]);
+    /// assert_eq!(Cell::from_bytes(cell.to_bytes()), cell);
+    /// ```
+    pub fn to_bytes(&self) -> [u8; 9] {
+        let ([fr, fg, fb, fa], [br, bg, bb, ba]): ([u8; 4], [u8; 4]) = (self.fg.into(), self.bg.into());
+        [self.ch, fr, fg, fb, fa, br, bg, bb, ba]
+    }
+
+    /// Unpack a cell from the layout `to_bytes` makes. The cell doesn't blink and has no style.
+    pub fn from_bytes(bytes: [u8; 9]) -> Cell {
+        let [ch, fr, fg, fb, fa, br, bg, bb, ba] = bytes;
+        Fg(Color::rgba(fr, fg, fb, fa)) + Bg(Color::rgba(br, bg, bb, ba)) + Char(ch)
+    }
+}
+
 macro_rules! apply_fields {
     ($t:ty { $($tfield:tt => $cfield:ident),+ }) => {
         impl BitOrAssign<$t> for Cell {
@@ -45,13 +108,14 @@ macro_rules! into_properties {
 }
 
 macro_rules! property_sum {
-    ($a:ty { $($afield:tt => $sumafield:ident),+ }, $b:ty { $($bfield:tt => $sumbfield:ident),+ } => $sum:ty ) => {
+    ($a:ty { $($afield:tt => $sumafield:ident),+ }, $b:ty { $($bfield:tt => $sumbfield:ident),+ } => $sum:ty $({ $($extra:ident: $default:expr),+ })? ) => {
         impl Add<$a> for $b {
             type Output = $sum;
             fn add(self, rhs: $a) -> Self::Output {
                 Self::Output {
                     $($sumafield: rhs.$afield,)+
                     $($sumbfield: self.$bfield,)+
+                    $($($extra: $default,)+)?
                 }
             }
         }
@@ -62,6 +126,7 @@ macro_rules! property_sum {
                 Self::Output {
                     $($sumafield: self.$afield,)+
                     $($sumbfield: rhs.$bfield,)+
+                    $($($extra: $default,)+)?
                 }
             }
         }
@@ -89,6 +154,13 @@ macro_rules! property_sum {
 /// let mut player = Fg(WHITE) + Bg(BLUE) + Char('@' as u8);
 /// player |= Fg(BLACK) + Bg(RED); // Change its color
 /// ```
+/// Cells built from `Fg`, `Bg`, and `Char` don't blink; add a `Blink` to a finished `Cell` (or
+/// apply one with `|=`) to change that:
+/// ```
+/// # use heart437::*;
+/// let alert = Fg(RED) + Bg(BLACK) + Char(b'!') + Blink(true);
+/// assert!(alert.blink);
+/// ```
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Fg(pub Color);
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -96,6 +168,8 @@ pub struct Bg(pub Color);
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Char(pub u8);
 #[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Blink(pub bool);
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct FgBg { fg: Color, bg: Color }
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct FgChar { fg: Color, ch: u8 }
@@ -105,22 +179,25 @@ pub struct BgChar { bg: Color, ch: u8 }
 property_sum!(Fg { 0 => fg }, Bg { 0 => bg } => FgBg);
 property_sum!(Fg { 0 => fg }, Char { 0 => ch } => FgChar);
 property_sum!(Bg { 0 => bg }, Char { 0 => ch } => BgChar);
-property_sum!(Fg { 0 => fg }, BgChar { bg => bg, ch => ch } => Cell);
-property_sum!(Bg { 0 => bg }, FgChar { fg => fg, ch => ch } => Cell);
-property_sum!(Char { 0 => ch }, FgBg { fg => fg, bg => bg } => Cell);
+property_sum!(Fg { 0 => fg }, BgChar { bg => bg, ch => ch } => Cell { blink: false, style: Style::NONE });
+property_sum!(Bg { 0 => bg }, FgChar { fg => fg, ch => ch } => Cell { blink: false, style: Style::NONE });
+property_sum!(Char { 0 => ch }, FgBg { fg => fg, bg => bg } => Cell { blink: false, style: Style::NONE });
+property_sum!(Blink { 0 => blink }, Cell { fg => fg, bg => bg, ch => ch, style => style } => Cell);
 apply_fields!(Fg { 0 => fg });
 apply_fields!(Bg { 0 => bg });
 apply_fields!(Char { 0 => ch });
 apply_fields!(FgBg { fg => fg, bg => bg });
 apply_fields!(FgChar { fg => fg, ch => ch });
 apply_fields!(BgChar { bg => bg, ch => ch });
-apply_fields!(Cell { fg => fg, bg => bg, ch => ch });
+apply_fields!(Blink { 0 => blink });
+apply_fields!(Cell { fg => fg, bg => bg, ch => ch, blink => blink, style => style });
 into_properties!(Cell, Fg => { fg });
 into_properties!(Cell, Bg => { bg });
 into_properties!(Cell, Char => { ch });
 into_properties!(Cell, FgBg => { fg, bg });
 into_properties!(Cell, FgChar => { fg, ch });
 into_properties!(Cell, BgChar => { bg, ch });
+into_properties!(Cell, Blink => { blink });
 into_properties!(FgBg, Fg => { fg });
 into_properties!(FgBg, Bg => { bg });
 into_properties!(FgChar, Fg => { fg });
@@ -128,6 +205,185 @@ into_properties!(FgChar, Char => { ch });
 into_properties!(BgChar, Bg => { bg });
 into_properties!(BgChar, Char => { ch });
 
+/// A set of optional changes to a cell: fields that are `Some` replace the cell's, and fields
+/// that are `None` leave it alone. Build one up as a "brush" and apply it to as many cells as you
+/// like with `|=` or `Layer::set`:
+/// ```
+/// # use heart437::*;
+/// let highlight = OptionCell::new().with_fg(YELLOW).with_bg(BLUE);
+/// let mut cell = Fg(WHITE) + Bg(BLACK) + Char(b'@');
+/// cell |= highlight;
+/// assert_eq!(cell, Fg(YELLOW) + Bg(BLUE) + Char(b'@'));
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub struct OptionCell {
+    pub ch: Option<u8>,
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+}
+
+impl OptionCell {
+    /// An `OptionCell` that changes nothing
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also set the character
+    pub fn with_ch(self, ch: u8) -> Self {
+        Self { ch: Some(ch), ..self }
+    }
+
+    /// Also set the foreground
+    pub fn with_fg(self, fg: Color) -> Self {
+        Self { fg: Some(fg), ..self }
+    }
+
+    /// Also set the background
+    pub fn with_bg(self, bg: Color) -> Self {
+        Self { bg: Some(bg), ..self }
+    }
+
+    /// Stack another `OptionCell` on top of this one: its `Some` fields win, and wherever it has
+    /// `None` this one's field falls through. Applying the result is the same as applying this
+    /// and then `over`.
+    /// ```
+    /// # use heart437::*;
+    /// let water = OptionCell::new().with_bg(BLUE);
+    /// let wave = OptionCell::new().with_ch(b'~').with_fg(WHITE);
+    /// assert_eq!(water.merge(wave), OptionCell::new().with_ch(b'~').with_fg(WHITE).with_bg(BLUE));
+    /// ```
+    pub fn merge(&self, over: OptionCell) -> OptionCell {
+        OptionCell {
+            ch: over.ch.or(self.ch),
+            fg: over.fg.or(self.fg),
+            bg: over.bg.or(self.bg),
+        }
+    }
+}
+
+impl BitOrAssign<OptionCell> for Cell {
+    fn bitor_assign(&mut self, rhs: OptionCell) {
+        if let Some(ch) = rhs.ch { self.ch = ch }
+        if let Some(fg) = rhs.fg { self.fg = fg }
+        if let Some(bg) = rhs.bg { self.bg = bg }
+    }
+}
+
+/// The Unicode equivalent of each CP437 glyph, in order
+pub(crate) const CP437: [char; 256] = [
+    '\0', '☺', '☻', '♥', '♦', '♣', '♠', '•', '◘', '○', '◙', '♂', '♀', '♪', '♫', '☼',
+    '►', '◄', '↕', '‼', '¶', '§', '▬', '↨', '↑', '↓', '→', '←', '∟', '↔', '▲', '▼',
+    ' ', '!', '"', '#', '$', '%', '&', '\'', '(', ')', '*', '+', ',', '-', '.', '/',
+    '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', ':', ';', '<', '=', '>', '?',
+    '@', 'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J', 'K', 'L', 'M', 'N', 'O',
+    'P', 'Q', 'R', 'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z', '[', '\\', ']', '^', '_',
+    '`', 'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o',
+    'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z', '{', '|', '}', '~', '⌂',
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å',
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ',
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»',
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐',
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧',
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀',
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩',
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}',
+];
+
+impl Char {
+    /// The non-panicking way to make a `Char` from a `char`: `None` if it's not ASCII.
+    /// (There's no `TryFrom<char>`, since the `From<char>` impl already provides one that can't fail.)
+    /// ```
+    /// # use heart437::*;
+    /// assert_eq!(Char::from_ascii('@'), Some(Char(b'@')));
+    /// assert_eq!(Char::from_ascii('☺'), None);
+    /// ```
+    pub fn from_ascii(ch: char) -> Option<Char> {
+        if ch.is_ascii() { Some(Char(ch as u8)) } else { None }
+    }
+
+    /// The `Char` whose CP437 glyph is a given Unicode char, like `'█'` for 0xdb or `'☺'` for 0x01,
+    /// or `None` if CP437 doesn't have it. Printable ASCII maps to itself. Use this to read text or
+    /// art with box-drawing and block characters in it; `'█' as u8` is not the same thing!
+    /// ```
+    /// # use heart437::*;
+    /// assert_eq!(Char::from_cp437_char('█'), Some(Char(0xdb)));
+    /// assert_eq!(Char::from_cp437_char('A'), Some(Char(b'A')));
+    /// assert_eq!(Char::from_cp437_char('✓'), None);
+    /// ```
+    pub fn from_cp437_char(ch: char) -> Option<Char> {
+        CP437.iter().position(|c| *c == ch).map(|n| Char(n as u8))
+    }
+
+    /// The Unicode char that looks like this `Char`'s CP437 glyph; the reverse of `from_cp437_char`
+    pub fn to_cp437_char(&self) -> char {
+        CP437[self.0 as usize]
+    }
+}
+
+impl From<char> for Char {
+    /// Make a `Char` from a `char`. Fonts are only defined for ASCII chars; will panic if passed a
+    /// non-ASCII char!
+    /// ```
+    /// # use heart437::*;
+    /// let player = Fg(WHITE) + Bg(BLUE) + Char::from('@');
+    /// ```
+    fn from(ch: char) -> Self {
+        assert!(ch.is_ascii(), "Fonts are only defined for ASCII chars!");
+        Char(ch as u8)
+    }
+}
+
+/// Style flags for a cell, which change how `Layer` draws it. Combine flags with `|`, and add a
+/// `Style` to a `Cell` (or apply one with `|=`) like the other components; either way it replaces
+/// the cell's whole style rather than adding flags to it.
+/// ```
+/// # use heart437::*;
+/// let mut title = Fg(WHITE) + Bg(BLUE) + Char(b'T') + (Style::BOLD | Style::UNDERLINE);
+/// assert!(title.style.contains(Style::BOLD));
+/// title |= Style::NONE; // Back to plain
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub struct Style(pub u8);
+
+impl Style {
+    /// No styling at all
+    pub const NONE: Style = Style(0);
+    /// Thicken the glyph by drawing it again one pixel to the right
+    pub const BOLD: Style = Style(1);
+    /// Turn on the bottom row of the glyph
+    pub const UNDERLINE: Style = Style(2);
+    /// Swap the foreground and background colors
+    pub const REVERSE: Style = Style(4);
+
+    /// Whether every flag set in `other` is also set in this style
+    pub fn contains(&self, other: Style) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for Style {
+    type Output = Style;
+    fn bitor(self, rhs: Style) -> Style { Style(self.0 | rhs.0) }
+}
+
+impl Add<Style> for Cell {
+    type Output = Cell;
+    fn add(self, rhs: Style) -> Cell { Cell { style: rhs, ..self } }
+}
+
+impl Add<Cell> for Style {
+    type Output = Cell;
+    fn add(self, rhs: Cell) -> Cell { Cell { style: self, ..rhs } }
+}
+
+impl BitOrAssign<Style> for Cell {
+    fn bitor_assign(&mut self, rhs: Style) { self.style = rhs }
+}
+
+impl From<Cell> for Style {
+    fn from(value: Cell) -> Self { value.style }
+}
+
 #[cfg(test)]
 mod test {
     use crate::{BLUE, RED, YELLOW};
@@ -142,7 +398,7 @@ mod test {
         assert_eq!(f + b, b + f);
         assert_eq!(f + b, FgBg { fg: RED, bg: WHITE });
         assert_eq!(f + b + ch, (f + ch) + b);
-        assert_eq!(f + b + ch, Cell { fg: RED, bg: WHITE, ch: 65u8 });
+        assert_eq!(f + b + ch, Cell { fg: RED, bg: WHITE, ch: 65u8, blink: false, style: Style::NONE });
     }
 
     #[test]
@@ -166,4 +422,128 @@ mod test {
         let col = Fg(RED) + Bg(WHITE);
         assert_eq!(Bg::from(col), Bg(WHITE));
     }
+
+    #[test]
+    fn test_clear() {
+        let mut a = Fg(RED) + Bg(BLUE) + Char(65);
+        a.clear_fg();
+        assert_eq!(a, Fg(WHITE) + Bg(BLUE) + Char(65));
+        a.clear_bg();
+        assert_eq!(a, Fg(WHITE) + Bg(CLEAR) + Char(65));
+        a.clear_char();
+        assert_eq!(a, Cell::default());
+    }
+
+    #[test]
+    fn test_blink() {
+        let c = Fg(RED) + Bg(WHITE) + Char(65);
+        assert!(!c.blink);
+        assert_eq!(Blink::from(c), Blink(false));
+
+        let b = c + Blink(true);
+        assert_eq!(b, Blink(true) + c);
+        assert_eq!(b, Cell { fg: RED, bg: WHITE, ch: 65, blink: true, style: Style::NONE });
+
+        let mut a = c;
+        a |= Blink(true);
+        assert_eq!(a, b);
+        a |= Fg(YELLOW) + Bg(BLUE);
+        assert!(a.blink);
+        a |= c;
+        assert_eq!(a, c);
+    }
+
+    #[test]
+    fn test_style() {
+        let both = Style::BOLD | Style::REVERSE;
+        assert!(both.contains(Style::BOLD) && both.contains(Style::REVERSE));
+        assert!(!both.contains(Style::UNDERLINE));
+        assert!(both.contains(Style::NONE));
+
+        let c = Fg(RED) + Bg(WHITE) + Char(65);
+        assert_eq!(c.style, Style::NONE);
+        let styled = c + Style::UNDERLINE + Blink(true);
+        assert_eq!(styled, Blink(true) + (Style::UNDERLINE + c));
+        assert_eq!(Style::from(styled), Style::UNDERLINE);
+
+        let mut a = styled;
+        a |= Style::BOLD;
+        assert_eq!(a.style, Style::BOLD);
+        a |= Fg(YELLOW);
+        assert_eq!(a.style, Style::BOLD);
+        a |= c;
+        assert_eq!(a, c);
+    }
+
+    #[test]
+    fn test_reversed() {
+        let c = Fg(RED) + Bg(WHITE) + Char(65) + Blink(true);
+        assert_eq!(c.reversed(), Fg(WHITE) + Bg(RED) + Char(65) + Blink(true));
+        assert_eq!(c.reversed().reversed(), c);
+    }
+
+    #[test]
+    fn test_char_from() {
+        assert_eq!(Char::from('A'), Char(65));
+        assert_eq!(Char::from_ascii('A'), Some(Char(65)));
+        assert_eq!(Char::from_ascii('é'), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_char_from_non_ascii() {
+        let _ = Char::from('é');
+    }
+
+    #[test]
+    fn test_bytes() {
+        let c = Fg(Color::rgba(1, 2, 3, 4)) + Bg(Color::rgba(5, 6, 7, 8)) + Char(9);
+        assert_eq!(c.to_bytes(), [9, 1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(Cell::from_bytes(c.to_bytes()), c);
+        assert_eq!(Cell::from_bytes([0; 9]), Fg(CLEAR) + Bg(CLEAR) + Char(0));
+
+        // Only the colors and char survive:
+        let fancy = c + Blink(true) + Style::BOLD;
+        assert_eq!(Cell::from_bytes(fancy.to_bytes()), c);
+    }
+
+    #[test]
+    fn test_option_cell() {
+        assert_eq!(OptionCell::new(), OptionCell { ch: None, fg: None, bg: None });
+        let brush = OptionCell::new().with_ch(b'#').with_bg(BLUE);
+        assert_eq!(brush, OptionCell { ch: Some(b'#'), fg: None, bg: Some(BLUE) });
+
+        let mut a = Fg(RED) + Bg(WHITE) + Char(65) + Blink(true);
+        a |= brush;
+        assert_eq!(a, Fg(RED) + Bg(BLUE) + Char(b'#') + Blink(true));
+        a |= OptionCell::new();
+        assert_eq!(a, Fg(RED) + Bg(BLUE) + Char(b'#') + Blink(true));
+    }
+
+    #[test]
+    fn test_option_cell_merge() {
+        let base = OptionCell::new().with_fg(RED).with_bg(WHITE);
+        let over = OptionCell::new().with_ch(b'#').with_bg(BLUE);
+        assert_eq!(base.merge(over), OptionCell { ch: Some(b'#'), fg: Some(RED), bg: Some(BLUE) });
+        assert_eq!(base.merge(OptionCell::new()), base);
+        assert_eq!(OptionCell::new().merge(base), base);
+
+        let (mut a, mut b) = (Cell::default(), Cell::default());
+        a |= base;
+        a |= over;
+        b |= base.merge(over);
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn test_cp437_chars() {
+        assert_eq!(Char(0x01).to_cp437_char(), '☺');
+        assert_eq!(Char(0xc9).to_cp437_char(), '╔');
+        assert_eq!(Char(b'~').to_cp437_char(), '~');
+        assert_eq!(Char::from_cp437_char('░'), Some(Char(0xb0)));
+        assert_eq!(Char::from_cp437_char('\n'), None);
+        for n in 0..=255 {
+            assert_eq!(Char::from_cp437_char(Char(n).to_cp437_char()), Some(Char(n)));
+        }
+    }
 }
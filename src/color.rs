@@ -1,5 +1,19 @@
+use std::fmt::{Display, Formatter};
 use image::Rgba;
 
+/// How `Color::blend_with` combines a color with what's already under it
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BlendMode {
+    /// Normal alpha compositing, the same as `blend_into`
+    Over,
+    /// Multiply the channels, which only ever darkens; good for shadows
+    Multiply,
+    /// The inverse of multiply, which only ever lightens
+    Screen,
+    /// Add the channels, saturating at 255; good for glowing light sources
+    Add,
+}
+
 /// A simple Color struct
 /// ```
 /// let slashdot = heart437::Color::rgba(0, 102, 102, 255);
@@ -17,25 +31,382 @@ impl Color {
         Self { r, g, b, a }
     }
 
-    /// Return the RGBA bytes of this color laid over an opaque background of another color.
-    /// The bg arg is a [u8; 4] but only the first three bytes (r, g, b) matter.
+    /// Parse a color from a CSS-style hex string: `"#RRGGBB"`, `"#RRGGBBAA"`, or the short
+    /// `"#RGB"`. Alpha is 255 unless it's given. The `#` is optional.
+    /// ```
+    /// # use heart437::*;
+    /// assert_eq!(Color::from_hex("#ff9900"), Ok(Color::rgba(0xff, 0x99, 0x00, 0xff)));
+    /// assert_eq!(Color::from_hex("#f90"), Ok(Color::rgba(0xff, 0x99, 0x00, 0xff)));
+    /// assert!(Color::from_hex("#ff99").is_err());
+    /// ```
+    pub fn from_hex(hex: &str) -> Result<Color, ColorParseError> {
+        let digits = hex.strip_prefix('#').unwrap_or(hex);
+        if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(ColorParseError::BadDigit)
+        }
+
+        let byte = |n: usize| u8::from_str_radix(&digits[n * 2 .. n * 2 + 2], 16).unwrap();
+        let nybble = |n: usize| u8::from_str_radix(&digits[n .. n + 1], 16).unwrap() * 0x11;
+        match digits.len() {
+            3 => Ok(Color::rgba(nybble(0), nybble(1), nybble(2), 255)),
+            6 => Ok(Color::rgba(byte(0), byte(1), byte(2), 255)),
+            8 => Ok(Color::rgba(byte(0), byte(1), byte(2), byte(3))),
+            _ => Err(ColorParseError::BadLength)
+        }
+    }
+
+    /// Format this color as a hex string, `"#rrggbbaa"`, which `from_hex` can read back
+    pub fn to_hex(&self) -> String {
+        format!("#{:02x}{:02x}{:02x}{:02x}", self.r, self.g, self.b, self.a)
+    }
+
+    /// The color of an xterm 256-color palette index: 0-15 are the system colors, 16-231 a
+    /// 6x6x6 color cube, and 232-255 a grayscale ramp. Always opaque.
+    /// ```
+    /// # use heart437::*;
+    /// assert_eq!(Color::from_ansi256(9), RED);
+    /// assert_eq!(Color::from_ansi256(208), Color::rgba(255, 135, 0, 255));
+    /// ```
+    pub fn from_ansi256(index: u8) -> Color {
+        const SYSTEM: [(u8, u8, u8); 16] = [
+            (0, 0, 0), (128, 0, 0), (0, 128, 0), (128, 128, 0),
+            (0, 0, 128), (128, 0, 128), (0, 128, 128), (192, 192, 192),
+            (128, 128, 128), (255, 0, 0), (0, 255, 0), (255, 255, 0),
+            (0, 0, 255), (255, 0, 255), (0, 255, 255), (255, 255, 255)
+        ];
+        const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+        match index {
+            0..=15 => {
+                let (r, g, b) = SYSTEM[index as usize];
+                Color::rgba(r, g, b, 255)
+            }
+            16..=231 => {
+                let i = (index - 16) as usize;
+                Color::rgba(LEVELS[i / 36], LEVELS[i / 6 % 6], LEVELS[i % 6], 255)
+            }
+            _ => {
+                let v = 8 + 10 * (index - 232);
+                Color::rgba(v, v, v, 255)
+            }
+        }
+    }
+
+    /// The xterm 256-color palette index closest to this color (ignoring alpha), for exporting
+    /// to terminals. Exact palette colors map back to themselves, preferring the lowest index.
+    pub fn to_ansi256(&self) -> u8 {
+        (0..=255u8).min_by_key(|i| self.distance_squared(&Color::from_ansi256(*i))).unwrap()
+    }
+
+    /// The entry in a palette closest to this color, by Euclidean distance in RGB. Alpha is
+    /// ignored, and ties go to whichever comes first. Panics on an empty palette!
+    /// ```
+    /// # use heart437::*;
+    /// let palette = [BLACK, WHITE, RED];
+    /// assert_eq!(Color::rgba(200, 30, 60, 255).nearest(&palette), RED);
+    /// ```
+    pub fn nearest(&self, palette: &[Color]) -> Color {
+        palette[self.nearest_index(palette)]
+    }
+
+    /// Like `nearest`, but the index of the closest palette entry rather than the entry itself
+    pub fn nearest_index(&self, palette: &[Color]) -> usize {
+        assert!(!palette.is_empty(), "Can't find the nearest color in an empty palette!");
+        (0..palette.len()).min_by_key(|i| self.distance_squared(&palette[*i])).unwrap()
+    }
+
+    /// Squared Euclidean distance between two colors' RGB channels
+    fn distance_squared(&self, other: &Color) -> i32 {
+        let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+        d(self.r, other.r) + d(self.g, other.g) + d(self.b, other.b)
+    }
+
+    /// A copy of this color with the alpha replaced; handy for fading something in or out
+    /// ```
+    /// # use heart437::*;
+    /// assert_eq!(RED.with_alpha(64), Color::rgba(255, 0, 0, 64));
+    /// ```
+    pub fn with_alpha(&self, a: u8) -> Color {
+        Color { a, ..*self }
+    }
+
+    /// A copy of this color with the red channel replaced
+    pub fn with_r(&self, r: u8) -> Color {
+        Color { r, ..*self }
+    }
+
+    /// A copy of this color with the green channel replaced
+    pub fn with_g(&self, g: u8) -> Color {
+        Color { g, ..*self }
+    }
+
+    /// A copy of this color with the blue channel replaced
+    pub fn with_b(&self, b: u8) -> Color {
+        Color { b, ..*self }
+    }
+
+    /// Look up a CSS color by name, ignoring case: the 16 HTML colors plus common extras like
+    /// "orange", "cyan", "magenta", "pink", "brown" and "transparent". Both "gray" and "grey" work.
+    /// These are the CSS values, so `named("purple")` and `named("green")` are darker than the
+    /// `PURPLE` and `GREEN` consts.
+    /// ```
+    /// # use heart437::*;
+    /// assert_eq!(Color::named("Orange"), Some(Color::rgba(255, 165, 0, 255)));
+    /// assert_eq!(Color::named("plaid"), None);
+    /// ```
+    pub fn named(name: &str) -> Option<Color> {
+        let (r, g, b, a) = match name.to_ascii_lowercase().as_str() {
+            "black" => (0, 0, 0, 255),
+            "silver" => (192, 192, 192, 255),
+            "gray" | "grey" => (128, 128, 128, 255),
+            "white" => (255, 255, 255, 255),
+            "maroon" => (128, 0, 0, 255),
+            "red" => (255, 0, 0, 255),
+            "purple" => (128, 0, 128, 255),
+            "fuchsia" | "magenta" => (255, 0, 255, 255),
+            "green" => (0, 128, 0, 255),
+            "lime" => (0, 255, 0, 255),
+            "olive" => (128, 128, 0, 255),
+            "yellow" => (255, 255, 0, 255),
+            "navy" => (0, 0, 128, 255),
+            "blue" => (0, 0, 255, 255),
+            "teal" => (0, 128, 128, 255),
+            "aqua" | "cyan" => (0, 255, 255, 255),
+            "orange" => (255, 165, 0, 255),
+            "pink" => (255, 192, 203, 255),
+            "brown" => (165, 42, 42, 255),
+            "gold" => (255, 215, 0, 255),
+            "indigo" => (75, 0, 130, 255),
+            "violet" => (238, 130, 238, 255),
+            "darkgray" | "darkgrey" => (169, 169, 169, 255),
+            "lightgray" | "lightgrey" => (211, 211, 211, 255),
+            "transparent" => (0, 0, 0, 0),
+            _ => return None,
+        };
+        Some(Color::rgba(r, g, b, a))
+    }
+
+    /// Create a color from hue (0..360), saturation (0..1), and value (0..1), plus alpha.
+    /// Out-of-range values are clamped, not wrapped.
+    /// ```
+    /// # use heart437::*;
+    /// assert_eq!(Color::from_hsv(120.0, 1.0, 1.0, 255), GREEN);
+    /// ```
+    pub fn from_hsv(h: f32, s: f32, v: f32, a: u8) -> Color {
+        let (h, s, v) = (h.clamp(0.0, 360.0), s.clamp(0.0, 1.0), v.clamp(0.0, 1.0));
+        let chroma = v * s;
+        let sector = h / 60.0;
+        let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+        let (r, g, b) = match sector as i32 % 6 {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+        let m = v - chroma;
+        let channel = |c: f32| ((c + m) * 255.0).round() as u8;
+        Color::rgba(channel(r), channel(g), channel(b), a)
+    }
+
+    /// Convert this color to hue (0..360), saturation (0..1), and value (0..1), ignoring alpha.
+    /// Grays (with no saturation) have a hue of 0.
+    pub fn to_hsv(&self) -> (f32, f32, f32) {
+        let (r, g, b) = (self.r as f32 / 255.0, self.g as f32 / 255.0, self.b as f32 / 255.0);
+        let max = r.max(g).max(b);
+        let chroma = max - r.min(g).min(b);
+
+        let hue = if chroma == 0.0 {
+            0.0
+        } else if max == r {
+            60.0 * ((g - b) / chroma).rem_euclid(6.0)
+        } else if max == g {
+            60.0 * ((b - r) / chroma + 2.0)
+        } else {
+            60.0 * ((r - g) / chroma + 4.0)
+        };
+        let saturation = if max == 0.0 { 0.0 } else { chroma / max };
+        (hue, saturation, max)
+    }
+
+    /// Linearly interpolate each channel (including alpha) between this color, at t = 0, and
+    /// another, at t = 1. `t` is clamped to 0..1.
+    /// ```
+    /// # use heart437::*;
+    /// assert_eq!(BLACK.lerp(WHITE, 0.5), Color::rgba(128, 128, 128, 255));
+    /// ```
+    pub fn lerp(&self, other: Color, t: f32) -> Color {
+        let t = t.clamp(0.0, 1.0);
+        let channel = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+        Color::rgba(channel(self.r, other.r), channel(self.g, other.g),
+                    channel(self.b, other.b), channel(self.a, other.a))
+    }
+
+    /// The weighted average of several colors, all four channels. The weights don't have to add
+    /// up to anything, they're normalized; negative weights count as 0. If there are no colors,
+    /// or they all have zero weight, this returns `CLEAR`.
+    /// ```
+    /// # use heart437::*;
+    /// let lamps = [(RED, 3.0), (BLUE, 1.0)];
+    /// assert_eq!(Color::mix(&lamps), Color::rgba(191, 0, 64, 255));
+    /// ```
+    pub fn mix(colors: &[(Color, f32)]) -> Color {
+        let total: f32 = colors.iter().map(|(_, w)| w.max(0.0)).sum();
+        if total <= 0.0 { return CLEAR }
+        let channel = |f: fn(&Color) -> u8| {
+            let sum: f32 = colors.iter().map(|(c, w)| f(c) as f32 * w.max(0.0)).sum();
+            (sum / total).round().clamp(0.0, 255.0) as u8
+        };
+        Color::rgba(channel(|c| c.r), channel(|c| c.g), channel(|c| c.b), channel(|c| c.a))
+    }
+
+    /// Move each RGB channel toward black by some amount 0..1, leaving alpha alone
+    /// ```
+    /// # use heart437::*;
+    /// let shadow = Color::rgba(200, 100, 50, 255).darken(0.5);
+    /// assert_eq!(shadow, Color::rgba(100, 50, 25, 255));
+    /// ```
+    pub fn darken(&self, amount: f32) -> Color {
+        self.lerp(Color::rgba(0, 0, 0, self.a), amount)
+    }
+
+    /// Move each RGB channel toward white by some amount 0..1, leaving alpha alone
+    pub fn lighten(&self, amount: f32) -> Color {
+        self.lerp(Color::rgba(255, 255, 255, self.a), amount)
+    }
+
+    /// The gray with the same perceived brightness as this color (using the 0.299 / 0.587 / 0.114
+    /// luminance weights), keeping its alpha. Good for drawing remembered-but-not-visible cells.
+    /// ```
+    /// # use heart437::*;
+    /// assert_eq!(RED.grayscale(), Color::rgba(76, 76, 76, 255));
+    /// ```
+    pub fn grayscale(&self) -> Color {
+        let luma = (0.299 * self.r as f32 + 0.587 * self.g as f32 + 0.114 * self.b as f32).round() as u8;
+        Color::rgba(luma, luma, luma, self.a)
+    }
+
+    /// Blend between this color (t = 0) and its `grayscale` (t = 1), for a dim but still tinted look
+    pub fn desaturate(&self, t: f32) -> Color {
+        self.lerp(self.grayscale(), t)
+    }
+
+    /// Flip each RGB channel (255 - channel), keeping alpha. Inverting twice gives the original color.
+    /// ```
+    /// # use heart437::*;
+    /// assert_eq!(YELLOW.invert(), BLUE);
+    /// ```
+    pub fn invert(&self) -> Color {
+        Color::rgba(255 - self.r, 255 - self.g, 255 - self.b, self.a)
+    }
+
+    /// Scale the alpha by an opacity 0..1, leaving the RGB channels alone
+    pub(crate) fn faded(&self, opacity: f32) -> Color {
+        Color { a: (self.a as f32 * opacity.clamp(0.0, 1.0)).round() as u8, ..*self }
+    }
+
+    /// Whether this color has 0 alpha, so drawing it does nothing
+    pub(crate) fn is_transparent(&self) -> bool {
+        self.a == 0
+    }
+
+    /// Scale the RGB channels by an intensity 0..1, leaving alpha alone
+    pub(crate) fn dimmed(&self, intensity: f32) -> Color {
+        let i = intensity.clamp(0.0, 1.0);
+        Self {
+            r: (self.r as f32 * i) as u8,
+            g: (self.g as f32 * i) as u8,
+            b: (self.b as f32 * i) as u8,
+            a: self.a
+        }
+    }
+
+    /// Return the RGBA bytes of this color laid over a background of another color, as a [u8; 4].
+    /// Over an opaque background the result is opaque; over a translucent one (like a pixel some
+    /// `CLEAR` cells were drawn to) this does proper "source over" compositing, so the result's
+    /// alpha is `a + bg_a * (1 - a)`.
     /// ```
     /// heart437::Color::rgba(0, 0, 0, 127).blend_into(&[0, 120, 160, 255]);
     /// ```
     pub fn blend_into(&self, bg: &[u8]) -> [u8; 4] {
+        self.blend_with(bg, BlendMode::Over)
+    }
+
+    /// Like `blend_into`, but mixing this color with the background using a `BlendMode` first.
+    /// The mixed color is then laid over the background using this color's alpha, so a
+    /// half-transparent `Multiply` only darkens halfway.
+    /// ```
+    /// # use heart437::*;
+    /// let shadow = Color::rgba(128, 128, 128, 255);
+    /// assert_eq!(shadow.blend_with(&[200, 100, 50, 255], BlendMode::Multiply), [100, 50, 25, 255]);
+    /// ```
+    pub fn blend_with(&self, bg: &[u8], mode: BlendMode) -> [u8; 4] {
+        if let [bgr, bgg, bgb, bga] = bg {
+            let mix = |fg: u8, bg: u8| -> u8 {
+                let (fg, bg) = (fg as f32 / 255.0, bg as f32 / 255.0);
+                let mixed = match mode {
+                    BlendMode::Over => fg,
+                    BlendMode::Multiply => fg * bg,
+                    BlendMode::Screen => 1.0 - (1.0 - fg) * (1.0 - bg),
+                    BlendMode::Add => (fg + bg).min(1.0),
+                };
+                // Where the background is translucent, the mode has less to work with:
+                let bg_a = *bga as f32 / 255.0;
+                ((fg * (1.0 - bg_a) + mixed * bg_a) * 255.0).round() as u8
+            };
+            match mode {
+                BlendMode::Over => self.blend_over(bg),
+                _ => Color::rgba(mix(self.r, *bgr), mix(self.g, *bgg), mix(self.b, *bgb), self.a).blend_over(bg)
+            }
+        } else {
+            panic!("Sir this is a Wendy's.")
+        }
+    }
+
+    /// Plain "source over" compositing, which is what both `blend_into` and `BlendMode::Over` do
+    fn blend_over(&self, bg: &[u8]) -> [u8; 4] {
         let a = (self.a as f32) / 255.0;
-        if let [bgr, bgg, bgb, _] = bg {
-            let mut out = [0, 0, 0, 255];
-            out[0] = ((self.r as f32 * a) + (*bgr as f32 * (1.0 - a))) as u8;
-            out[1] = ((self.g as f32 * a) + (*bgg as f32 * (1.0 - a))) as u8;
-            out[2] = ((self.b as f32 * a) + (*bgb as f32 * (1.0 - a))) as u8;
-            out
+        if let [bgr, bgg, bgb, bga] = bg {
+            if *bga == 255 {
+                let mut out = [0, 0, 0, 255];
+                out[0] = ((self.r as f32 * a) + (*bgr as f32 * (1.0 - a))) as u8;
+                out[1] = ((self.g as f32 * a) + (*bgg as f32 * (1.0 - a))) as u8;
+                out[2] = ((self.b as f32 * a) + (*bgb as f32 * (1.0 - a))) as u8;
+                out
+            } else {
+                let bg_a = (*bga as f32) / 255.0;
+                let out_a = a + bg_a * (1.0 - a);
+                if out_a == 0.0 { return [0, 0, 0, 0] }
+                let channel = |fg: u8, bg: u8| ((fg as f32 * a + bg as f32 * bg_a * (1.0 - a)) / out_a).round() as u8;
+                [channel(self.r, *bgr), channel(self.g, *bgg), channel(self.b, *bgb), (out_a * 255.0).round() as u8]
+            }
         } else {
             panic!("Sir this is a Wendy's.")
         }
     }
 }
 
+/// The ways `Color::from_hex` can fail
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ColorParseError {
+    /// There weren't 3, 6, or 8 digits
+    BadLength,
+    /// Something other than a hex digit was in the string
+    BadDigit
+}
+
+impl Display for ColorParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ColorParseError::BadLength => write!(f, "hex colors must have 3, 6, or 8 digits"),
+            ColorParseError::BadDigit => write!(f, "invalid hex digit in color"),
+        }
+    }
+}
+
+impl std::error::Error for ColorParseError {}
+
 pub const CLEAR: Color = Color { r: 0, g: 0, b: 0, a: 0 };
 pub const WHITE: Color = Color { r: 255, g: 255, b: 255, a: 255 };
 pub const BLACK: Color = Color { r: 0, g: 0, b: 0, a: 255 };
@@ -55,4 +426,277 @@ impl Into<[u8; 4]> for Color {
     fn into(self) -> [u8; 4] {
         [self.r, self.g, self.b, self.a]
     }
-}
\ No newline at end of file
+}
+
+/// With the `serde` feature, colors serialize as an `{r, g, b, a}` map. They deserialize from
+/// either that map (where `a` is optional and defaults to 255) or any string `from_hex` can read.
+/// Because of the string option this needs a self-describing format, like JSON or TOML.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Color {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("Color", 4)?;
+        state.serialize_field("r", &self.r)?;
+        state.serialize_field("g", &self.g)?;
+        state.serialize_field("b", &self.b)?;
+        state.serialize_field("a", &self.a)?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Color {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+        deserializer.deserialize_any(ColorVisitor)
+    }
+}
+
+#[cfg(feature = "serde")]
+struct ColorVisitor;
+
+#[cfg(feature = "serde")]
+impl<'de> serde::de::Visitor<'de> for ColorVisitor {
+    type Value = Color;
+
+    fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "an {{r, g, b, a}} map or a hex color string")
+    }
+
+    fn visit_str<E: serde::de::Error>(self, hex: &str) -> Result<Color, E> {
+        Color::from_hex(hex).map_err(E::custom)
+    }
+
+    fn visit_map<A: serde::de::MapAccess<'de>>(self, mut map: A) -> Result<Color, A::Error> {
+        use serde::de::Error;
+        const FIELDS: &[&str] = &["r", "g", "b", "a"];
+        let mut channels = [None; 4];
+        while let Some(key) = map.next_key::<String>()? {
+            let i = FIELDS.iter().position(|f| *f == key).ok_or_else(|| A::Error::unknown_field(&key, FIELDS))?;
+            if channels[i].is_some() { return Err(A::Error::duplicate_field(FIELDS[i])) }
+            channels[i] = Some(map.next_value::<u8>()?);
+        }
+        let channel = |i: usize| channels[i].ok_or_else(|| A::Error::missing_field(FIELDS[i]));
+        Ok(Color::rgba(channel(0)?, channel(1)?, channel(2)?, channels[3].unwrap_or(255)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_from_hex() {
+        assert_eq!(Color::from_hex("#ff9900"), Ok(Color::rgba(0xff, 0x99, 0x00, 0xff)));
+        assert_eq!(Color::from_hex("#FF990080"), Ok(Color::rgba(0xff, 0x99, 0x00, 0x80)));
+        assert_eq!(Color::from_hex("#f90"), Ok(Color::rgba(0xff, 0x99, 0x00, 0xff)));
+        assert_eq!(Color::from_hex("00ff00"), Ok(GREEN));
+
+        assert_eq!(Color::from_hex("#ff99"), Err(ColorParseError::BadLength));
+        assert_eq!(Color::from_hex(""), Err(ColorParseError::BadLength));
+        assert_eq!(Color::from_hex("#gg9900"), Err(ColorParseError::BadDigit));
+        assert_eq!(Color::from_hex("#ff990é"), Err(ColorParseError::BadDigit));
+    }
+
+    #[test]
+    fn test_to_hex() {
+        let c = Color::rgba(0x12, 0xab, 0x00, 0x7f);
+        assert_eq!(c.to_hex(), "#12ab007f");
+        assert_eq!(Color::from_hex(&c.to_hex()), Ok(c));
+    }
+
+    #[test]
+    fn test_hsv() {
+        assert_eq!(Color::from_hsv(0.0, 1.0, 1.0, 255), RED);
+        assert_eq!(Color::from_hsv(240.0, 1.0, 1.0, 255), BLUE);
+        assert_eq!(Color::from_hsv(300.0, 1.0, 1.0, 10), Color::rgba(255, 0, 255, 10));
+        assert_eq!(Color::from_hsv(30.0, 1.0, 1.0, 255), Color::rgba(255, 128, 0, 255));
+        assert_eq!(Color::from_hsv(360.0, 1.0, 1.0, 255), RED);
+
+        assert_eq!(RED.to_hsv(), (0.0, 1.0, 1.0));
+        assert_eq!(YELLOW.to_hsv(), (60.0, 1.0, 1.0));
+        assert_eq!(PURPLE.to_hsv(), (300.0, 1.0, 1.0));
+        assert_eq!(BLACK.to_hsv(), (0.0, 0.0, 0.0));
+
+        // Grays have no hue, and round-trip:
+        let gray = Color::rgba(128, 128, 128, 255);
+        let (h, s, v) = gray.to_hsv();
+        assert_eq!((h, s), (0.0, 0.0));
+        assert_eq!(Color::from_hsv(h, s, v, 255), gray);
+
+        // Out-of-range values clamp:
+        assert_eq!(Color::from_hsv(-30.0, 2.0, 5.0, 255), RED);
+        assert_eq!(Color::from_hsv(400.0, 1.0, 1.0, 255), RED);
+
+        let c = Color::rgba(12, 200, 99, 255);
+        let (h, s, v) = c.to_hsv();
+        assert_eq!(Color::from_hsv(h, s, v, 255), c);
+    }
+
+    #[test]
+    fn test_lerp() {
+        assert_eq!(RED.lerp(BLUE, 0.0), RED);
+        assert_eq!(RED.lerp(BLUE, 1.0), BLUE);
+        assert_eq!(RED.lerp(BLUE, 0.25), Color::rgba(191, 0, 64, 255));
+        assert_eq!(CLEAR.lerp(WHITE, 0.5), Color::rgba(128, 128, 128, 128));
+        assert_eq!(RED.lerp(BLUE, -1.0), RED);
+        assert_eq!(RED.lerp(BLUE, 2.0), BLUE);
+    }
+
+    #[test]
+    fn test_darken_lighten() {
+        let c = Color::rgba(200, 100, 50, 128);
+        assert_eq!(c.darken(0.0), c);
+        assert_eq!(c.darken(0.5), Color::rgba(100, 50, 25, 128));
+        assert_eq!(c.darken(1.0), Color::rgba(0, 0, 0, 128));
+        assert_eq!(c.lighten(0.5), Color::rgba(228, 178, 153, 128));
+        assert_eq!(c.lighten(1.0), Color::rgba(255, 255, 255, 128));
+        assert_eq!(c.lighten(3.0), Color::rgba(255, 255, 255, 128));
+        assert_eq!(c.darken(-1.0), c);
+    }
+
+    #[test]
+    fn test_grayscale() {
+        assert_eq!(WHITE.grayscale(), WHITE);
+        assert_eq!(BLACK.grayscale(), BLACK);
+        assert_eq!(GREEN.grayscale(), Color::rgba(150, 150, 150, 255));
+        assert_eq!(Color::rgba(0, 0, 255, 7).grayscale(), Color::rgba(29, 29, 29, 7));
+
+        assert_eq!(RED.desaturate(0.0), RED);
+        assert_eq!(RED.desaturate(1.0), RED.grayscale());
+        assert_eq!(RED.desaturate(0.5), Color::rgba(166, 38, 38, 255));
+    }
+
+    #[test]
+    fn test_blend_into() {
+        // Over an opaque background, nothing changes from the simple blend:
+        assert_eq!(Color::rgba(200, 0, 0, 51).blend_into(&[0, 0, 100, 255]), [40, 0, 80, 255]);
+        assert_eq!(CLEAR.blend_into(&[1, 2, 3, 255]), [1, 2, 3, 255]);
+
+        // Over nothing at all, we get the color itself:
+        assert_eq!(Color::rgba(255, 0, 0, 127).blend_into(&[0, 0, 0, 0]), [255, 0, 0, 127]);
+        assert_eq!(CLEAR.blend_into(&[0, 0, 0, 0]), [0, 0, 0, 0]);
+
+        // Two half-transparent colors make a 3/4-opaque one, weighted toward the top color:
+        let [r, g, b, a] = Color::rgba(255, 0, 0, 128).blend_into(&[0, 0, 255, 128]);
+        assert_eq!(a, 192);
+        assert!(r > b && b > 0 && g == 0);
+
+        // An opaque color always covers completely:
+        assert_eq!(BLUE.blend_into(&[200, 100, 0, 50]), [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn test_invert() {
+        assert_eq!(WHITE.invert(), BLACK);
+        assert_eq!(CLEAR.invert(), Color::rgba(255, 255, 255, 0));
+        let c = Color::rgba(12, 200, 99, 40);
+        assert_eq!(c.invert(), Color::rgba(243, 55, 156, 40));
+        assert_eq!(c.invert().invert(), c);
+    }
+
+    #[test]
+    fn test_named() {
+        assert_eq!(Color::named("red"), Some(RED));
+        assert_eq!(Color::named("WHITE"), Some(WHITE));
+        assert_eq!(Color::named("grey"), Color::named("gray"));
+        assert_eq!(Color::named("cyan"), Some(Color::rgba(0, 255, 255, 255)));
+        assert_eq!(Color::named("transparent"), Some(CLEAR));
+        assert_eq!(Color::named(""), None);
+        assert_eq!(Color::named("reddish"), None);
+    }
+
+    #[test]
+    fn test_with_channels() {
+        let c = Color::rgba(10, 20, 30, 40);
+        assert_eq!(c.with_alpha(255), Color::rgba(10, 20, 30, 255));
+        assert_eq!(c.with_r(0), Color::rgba(0, 20, 30, 40));
+        assert_eq!(c.with_g(0), Color::rgba(10, 0, 30, 40));
+        assert_eq!(c.with_b(0), Color::rgba(10, 20, 0, 40));
+        assert_eq!(WHITE.with_alpha(0).with_alpha(255), WHITE);
+    }
+
+    #[test]
+    fn test_blend_with() {
+        let bg = [200, 100, 50, 255];
+        let gray = Color::rgba(128, 128, 128, 255);
+        assert_eq!(gray.blend_with(&bg, BlendMode::Over), gray.blend_into(&bg));
+        assert_eq!(Color::rgba(100, 20, 3, 77).blend_with(&bg, BlendMode::Over), Color::rgba(100, 20, 3, 77).blend_into(&bg));
+        assert_eq!(gray.blend_with(&bg, BlendMode::Multiply), [100, 50, 25, 255]);
+        assert_eq!(gray.blend_with(&bg, BlendMode::Screen), [228, 178, 153, 255]);
+        assert_eq!(gray.blend_with(&bg, BlendMode::Add), [255, 228, 178, 255]);
+
+        // White multiplies to no change, black screens to no change:
+        assert_eq!(WHITE.blend_with(&bg, BlendMode::Multiply), bg);
+        assert_eq!(BLACK.blend_with(&bg, BlendMode::Screen), bg);
+
+        // A transparent color does nothing in any mode:
+        for mode in [BlendMode::Over, BlendMode::Multiply, BlendMode::Screen, BlendMode::Add] {
+            assert_eq!(CLEAR.blend_with(&bg, mode), bg);
+        }
+
+        // Over a totally transparent pixel, there's nothing to mix with:
+        assert_eq!(gray.blend_with(&[0, 0, 0, 0], BlendMode::Multiply), [128, 128, 128, 255]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let c = Color::rgba(255, 153, 0, 128);
+        let json = serde_json::to_string(&c).unwrap();
+        assert_eq!(json, r#"{"r":255,"g":153,"b":0,"a":128}"#);
+        assert_eq!(serde_json::from_str::<Color>(&json).unwrap(), c);
+
+        assert_eq!(serde_json::from_str::<Color>("\"#ff9900\"").unwrap(), Color::rgba(255, 153, 0, 255));
+        assert_eq!(serde_json::from_str::<Color>(r#"{"r": 1, "g": 2, "b": 3}"#).unwrap(), Color::rgba(1, 2, 3, 255));
+        assert!(serde_json::from_str::<Color>("\"#ff99\"").is_err());
+        assert!(serde_json::from_str::<Color>(r#"{"r": 1, "g": 2}"#).is_err());
+        assert!(serde_json::from_str::<Color>(r#"{"r": 1, "g": 2, "b": 3, "x": 4}"#).is_err());
+    }
+
+    #[test]
+    fn test_ansi256() {
+        assert_eq!(Color::from_ansi256(0), BLACK);
+        assert_eq!(Color::from_ansi256(15), WHITE);
+        assert_eq!(Color::from_ansi256(16), BLACK);
+        assert_eq!(Color::from_ansi256(21), BLUE);
+        assert_eq!(Color::from_ansi256(231), WHITE);
+        assert_eq!(Color::from_ansi256(232), Color::rgba(8, 8, 8, 255));
+        assert_eq!(Color::from_ansi256(255), Color::rgba(238, 238, 238, 255));
+
+        // Every palette color comes back to an index with the same color
+        for i in 0..=255 {
+            let c = Color::from_ansi256(i);
+            assert_eq!(Color::from_ansi256(c.to_ansi256()), c);
+        }
+        assert_eq!(RED.to_ansi256(), 9);
+        assert_eq!(Color::rgba(250, 130, 5, 0).to_ansi256(), 208);
+    }
+
+    #[test]
+    fn test_nearest() {
+        let palette = [BLACK, WHITE, RED, Color::rgba(255, 0, 0, 0)];
+        assert_eq!(Color::rgba(30, 30, 30, 255).nearest(&palette), BLACK);
+        assert_eq!(Color::rgba(200, 200, 190, 255).nearest_index(&palette), 1);
+
+        // Alpha doesn't count, so the first of the two reds wins:
+        assert_eq!(Color::rgba(255, 0, 0, 0).nearest_index(&palette), 2);
+
+        // Gray is equally far from blue and green, so the first one wins:
+        assert_eq!(Color::rgba(128, 128, 128, 255).nearest_index(&[BLUE, GREEN]), 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_nearest_empty() {
+        RED.nearest(&[]);
+    }
+
+    #[test]
+    fn test_mix() {
+        assert_eq!(Color::mix(&[]), CLEAR);
+        assert_eq!(Color::mix(&[(RED, 0.0)]), CLEAR);
+        assert_eq!(Color::mix(&[(RED, 0.25)]), RED);
+        assert_eq!(Color::mix(&[(RED, 1.0), (BLUE, 1.0)]), RED.lerp(BLUE, 0.5));
+        assert_eq!(Color::mix(&[(WHITE, 1.0), (CLEAR, 1.0), (GREEN, -5.0)]), Color::rgba(128, 128, 128, 128));
+    }
+}
@@ -1,7 +1,7 @@
 use std::fmt::Formatter;
 use std::hash::{Hash, Hasher};
 use std::ops::{Add, Div, Mul};
-use line_drawing::Point;
+use line_drawing::{Bresenham, Point};
 
 #[derive(Copy, Clone, PartialEq)]
 pub enum Dir { North, South, East, West }
@@ -71,6 +71,67 @@ impl Coord {
         other == self.northeast() || other == self.northwest() ||
             other == self.southeast() || other == self.southwest()
     }
+
+    /// The cells of a straight line from this coord to another, both ends included, by
+    /// Bresenham's algorithm. Each step moves to one of the eight neighbors.
+    /// ```
+    /// # use heart437::*;
+    /// assert_eq!(xy(0, 0).line_to(xy(3, 1)), vec![xy(0, 0), xy(1, 0), xy(2, 0), xy(3, 1)]);
+    /// ```
+    pub fn line_to(&self, other: Coord) -> Vec<Coord> {
+        Bresenham::new((*self).into(), other.into()).map(Coord::from).collect()
+    }
+
+    /// Iterate over the coords exactly `radius` away (Chebyshev distance) from this one: a square
+    /// ring, clockwise from its top-left corner. A radius of 0 is just this coord, and a negative
+    /// one is nothing. This doesn't know about any grid, so filter with `grid.contains`.
+    /// ```
+    /// # use heart437::*;
+    /// let ring: Vec<Coord> = xy(5, 5).ring(1).collect();
+    /// assert_eq!(ring.len(), 8);
+    /// assert_eq!(&ring[0..3], &[xy(4, 4), xy(5, 4), xy(6, 4)]);
+    /// ```
+    pub fn ring(&self, radius: i32) -> impl Iterator<Item=Coord> {
+        let Coord(x, y) = *self;
+        let side = (2 * radius).max(1);
+        let count = if radius == 0 { 1 } else { 8 * radius };
+
+        (0..count).map(move |n| {
+            let i = n % side;
+            match n / side {
+                0 => xy(x - radius + i, y - radius), // Top, going east
+                1 => xy(x + radius, y - radius + i), // Right, going south
+                2 => xy(x + radius - i, y + radius), // Bottom, going west
+                _ => xy(x - radius, y + radius - i), // Left, going north
+            }
+        })
+    }
+
+    /// Iterate over every coord within a (Chebyshev) distance of this one, spiraling outward
+    /// clockwise from the center: first this coord, then each square ring around it, each ring
+    /// starting at its top-left corner. This is handy for finding the nearest open cell to a point.
+    /// ```
+    /// # use heart437::*;
+    /// let mut spiral = xy(5, 5).spiral(1);
+    /// assert_eq!(spiral.next(), Some(xy(5, 5)));
+    /// assert_eq!(spiral.next(), Some(xy(4, 4)));
+    /// assert_eq!(spiral.next(), Some(xy(5, 4)));
+    /// ```
+    pub fn spiral(&self, max_radius: i32) -> impl Iterator<Item=Coord> {
+        let center = *self;
+        (0..=max_radius).flat_map(move |r| center.ring(r))
+    }
+
+    /// Iterate over the coords of an arbitrary rectangle, in reading order. `start` is the
+    /// top-left corner and `end` is exclusive, just like iterating over a size:
+    /// ```
+    /// # use heart437::*;
+    /// let region: Vec<Coord> = Coord::iter_from(xy(2, 3), xy(4, 5)).collect();
+    /// assert_eq!(region, vec![xy(2, 3), xy(3, 3), xy(2, 4), xy(3, 4)]);
+    /// ```
+    pub fn iter_from(start: Coord, end: Coord) -> CoordIterator {
+        CoordIterator { start, end, curr: 0 }
+    }
 }
 
 impl std::fmt::Display for Coord {
@@ -139,6 +200,7 @@ impl From<Point<i32>> for Coord {
 }
 
 pub struct CoordIterator {
+    start: Coord,
     end: Coord,
     curr: i32
 }
@@ -147,10 +209,11 @@ impl Iterator for CoordIterator {
     type Item = Coord;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.end.0 <= 0 || self.end.1 <= 0 {
+        let (width, height) = (self.end.0 - self.start.0, self.end.1 - self.start.1);
+        if width <= 0 || height <= 0 {
             None
-        } else if self.curr < self.end.0 * self.end.1 {
-            let c = xy(self.curr % self.end.0, self.curr / self.end.0);
+        } else if self.curr < width * height {
+            let c = xy(self.start.0 + self.curr % width, self.start.1 + self.curr / width);
             self.curr += 1;
             Some(c)
         } else { None }
@@ -162,7 +225,7 @@ impl IntoIterator for Coord {
     type IntoIter = CoordIterator;
 
     fn into_iter(self) -> Self::IntoIter {
-        CoordIterator { end: self, curr: 0 }
+        Coord::iter_from(xy(0, 0), self)
     }
 }
 
@@ -177,4 +240,55 @@ mod test {
         assert_eq!(c.next(), Some(xy(1, 0)));
         assert_eq!(c.next(), None);
     }
+
+    #[test]
+    fn test_iter_from() {
+        let mut c = Coord::iter_from(xy(2, 3), xy(4, 5));
+        assert_eq!(c.next(), Some(xy(2, 3)));
+        assert_eq!(c.next(), Some(xy(3, 3)));
+        assert_eq!(c.next(), Some(xy(2, 4)));
+        assert_eq!(c.next(), Some(xy(3, 4)));
+        assert_eq!(c.next(), None);
+
+        // Empty or inverted rectangles yield nothing:
+        assert_eq!(Coord::iter_from(xy(4, 5), xy(2, 3)).next(), None);
+    }
+
+    #[test]
+    fn test_spiral() {
+        let spiral: Vec<Coord> = xy(3, 3).spiral(2).collect();
+        assert_eq!(spiral.len(), 25);
+        assert_eq!(spiral[0], xy(3, 3));
+        assert_eq!(&spiral[1..9], &[xy(2, 2), xy(3, 2), xy(4, 2), xy(4, 3),
+                                    xy(4, 4), xy(3, 4), xy(2, 4), xy(2, 3)]);
+
+        // Each cell in range exactly once:
+        for pt in Coord::iter_from(xy(1, 1), xy(6, 6)) {
+            assert_eq!(spiral.iter().filter(|c| **c == pt).count(), 1);
+        }
+
+        assert_eq!(xy(0, 0).spiral(0).collect::<Vec<_>>(), vec![xy(0, 0)]);
+        assert_eq!(xy(0, 0).spiral(-1).count(), 0);
+    }
+
+    #[test]
+    fn test_line_to() {
+        assert_eq!(xy(2, 2).line_to(xy(2, 2)), vec![xy(2, 2)]);
+        assert_eq!(xy(3, 1).line_to(xy(0, 1)), vec![xy(3, 1), xy(2, 1), xy(1, 1), xy(0, 1)]);
+        let line = xy(0, 0).line_to(xy(-7, 3));
+        assert_eq!((line[0], line[line.len() - 1], line.len()), (xy(0, 0), xy(-7, 3), 8));
+        assert!(line.windows(2).all(|w| w[0].adjacent(w[1]) || w[0].diagonal(w[1])));
+    }
+
+    #[test]
+    fn test_ring() {
+        assert_eq!(xy(1, 1).ring(0).collect::<Vec<_>>(), vec![xy(1, 1)]);
+        assert_eq!(xy(1, 1).ring(-2).count(), 0);
+
+        let ring: Vec<Coord> = xy(0, 0).ring(3).collect();
+        assert_eq!(ring.len(), 24);
+        assert!(ring.iter().all(|pt| pt.0.abs().max(pt.1.abs()) == 3));
+        assert!(ring.windows(2).all(|w| w[0].adjacent(w[1])));
+        assert_eq!((ring[0], ring[6], ring[12], ring[18]), (xy(-3, -3), xy(3, -3), xy(3, 3), xy(-3, 3)));
+    }
 }
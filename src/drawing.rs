@@ -1,12 +1,29 @@
-use crate::{Bg, Char, Color, Coord, Fg, Grid, xy};
+use crate::{Cell, Color, Coord, Grid, OptionCell, VecGrid, xy};
 use crate::layer::*;
+use crate::font::wrap;
 
 /// A Canvas is anything that we can set a cell on: anything that lets us put an optionally-colored
 /// ASCII char into a spot on a grid. `Layer` is a canvas.
+///
+/// `Layer` also has its own `set`, which applies an `OptionCell`, and method-call syntax finds
+/// that one first. To call this trait's `set` on a layer, write it as `Canvas::set`:
+/// ```
+/// # use heart437::*;
+/// # let font = Font::default();
+/// let mut layer = Layer::new(&font, xy(10, 10), pxy(1, 1), pxy(0, 0));
+/// Canvas::set(&mut layer, xy(1, 1), Some('x'), Some(RED), None);
+/// layer.set(xy(2, 1), OptionCell::new().with_ch(b'y').with_fg(RED));
+/// ```
 pub trait Canvas {
     /// Set a cell, optionally set the color as well
     fn set(&mut self, at: Coord, ch: Option<char>, fg: Option<Color>, bg: Option<Color>);
 
+    /// Like `set`, but with the glyph's slot in the font rather than a char, so it doesn't go
+    /// through a codepage. Things like `rect` use this to draw CP437 box-drawing glyphs.
+    fn set_glyph(&mut self, at: Coord, glyph: Option<u8>, fg: Option<Color>, bg: Option<Color>) {
+        self.set(at, glyph.map(|g| g as char), fg, bg)
+    }
+
     /// Return the size of the grid
     fn size(&self) -> Coord;
 
@@ -14,7 +31,7 @@ pub trait Canvas {
     fn within(&self, point: Coord) -> bool {
         let Coord(x, y) = point;
         let Coord(xmax, ymax) = self.size();
-        x < xmax && y < ymax
+        x >= 0 && y >= 0 && x < xmax && y < ymax
     }
 
     /// Fill a rectangle with a single character. The rectangle is clipped to the region of the canvas.
@@ -34,35 +51,348 @@ pub trait Canvas {
         }
     }
 
+    /// Draw a straight line of cells from one coord to another, both ends included, clipped to
+    /// the region of the canvas. The cells are the ones `Coord::line_to` returns, for reusing the
+    /// path in game logic.
+    /// ```
+    /// # use heart437::*;
+    /// # let font = Font::default();
+    /// let mut layer = Layer::new(&font, xy(80, 25), pxy(1, 1), pxy(0, 0));
+    /// layer.line(xy(10, 10), xy(30, 4), Some('*'), Some(RED), None);
+    /// ```
+    fn line(&mut self, from: Coord, to: Coord, ch: Option<char>, fg: Option<Color>, bg: Option<Color>) {
+        for pt in from.line_to(to) {
+            if self.within(pt) {
+                self.set(pt, ch, fg, bg)
+            }
+        }
+    }
+
+    /// Draw a horizontal run of `len` cells, rightward from `pos`, clipped to the region of the
+    /// canvas. A length of zero or less draws nothing.
+    /// ```
+    /// # use heart437::*;
+    /// # let font = Font::default();
+    /// let mut layer = Layer::new(&font, xy(80, 25), pxy(1, 1), pxy(0, 0));
+    /// layer.hline(xy(2, 3), 20, Some('-'), Some(WHITE), None);
+    /// ```
+    fn hline(&mut self, pos: Coord, len: i32, ch: Option<char>, fg: Option<Color>, bg: Option<Color>) {
+        self.fill_rect(ch, fg, bg, pos, xy(len, 1))
+    }
+
+    /// Draw a vertical run of `len` cells, downward from `pos`, clipped to the region of the
+    /// canvas. A length of zero or less draws nothing.
+    fn vline(&mut self, pos: Coord, len: i32, ch: Option<char>, fg: Option<Color>, bg: Option<Color>) {
+        self.fill_rect(ch, fg, bg, pos, xy(1, len))
+    }
+
+    /// Fill a rectangle's background with a gradient from one color to another, left to right if
+    /// `horizontal` and top to bottom otherwise. The first and last columns (or rows) get exactly
+    /// `from` and `to`. Chars and foregrounds are left alone, and the rectangle is clipped to the
+    /// region of the canvas.
+    /// ```
+    /// # use heart437::*;
+    /// # let font = Font::default();
+    /// let mut layer = Layer::new(&font, xy(80, 25), pxy(1, 1), pxy(0, 0));
+    /// layer.fill_gradient(xy(0, 0), xy(80, 10), BLUE, Color::rgba(255, 160, 80, 255), false);
+    /// ```
+    fn fill_gradient(&mut self, pos: Coord, size: Coord, from: Color, to: Color, horizontal: bool) {
+        let steps = if horizontal { size.0 } else { size.1 } - 1;
+        for y in pos.1 .. (pos.1 + size.1) {
+            for x in pos.0 .. (pos.0 + size.0) {
+                if self.within(xy(x, y)) {
+                    let step = if horizontal { x - pos.0 } else { y - pos.1 };
+                    let t = if steps > 0 { step as f32 / steps as f32 } else { 0.0 };
+                    self.set(xy(x, y), None, None, Some(from.lerp(to, t)))
+                }
+            }
+        }
+    }
+
     /// Fill with a given char / color
     fn fill(&mut self, ch: Option<char>, fg: Option<Color>, bg: Option<Color>) {
         self.fill_rect(ch, fg, bg, xy(0, 0), self.size())
     }
 
+    /// Set a cell to the code page 437 shade glyph closest to a brightness from 0 (a space) to 1
+    /// (a full block), with the light, medium, and dark shades between. Levels outside 0..1 are
+    /// clamped. Handy for heatmaps and lighting falloff drawn purely in text.
+    /// ```
+    /// # use heart437::*;
+    /// # let font = Font::default();
+    /// let mut layer = Layer::new(&font, xy(80, 25), pxy(1, 1), pxy(0, 0));
+    /// layer.shade(xy(0, 0), 0.5, Some(YELLOW), Some(BLACK));
+    /// assert_eq!(layer[xy(0, 0)].ch, 0xb1);
+    /// ```
+    fn shade(&mut self, at: Coord, level: f32, fg: Option<Color>, bg: Option<Color>) {
+        const SHADES: [u8; 5] = [b' ', 0xb0, 0xb1, 0xb2, 0xdb];
+        let n = (level.clamp(0.0, 1.0) * 4.0).round() as usize;
+        self.set_glyph(at, Some(SHADES[n]), fg, bg)
+    }
+
+    /// Draw a whole grid of numbers with `shade`, for a quick look at something like a Dijkstra
+    /// map or a light map. The grid's lowest value draws as a space and its highest as a full
+    /// block; values that aren't finite (like unreachable cells) are skipped, and if every value
+    /// is the same they're all full blocks. The grid's top-left goes at `pos`, clipped to the
+    /// region of the canvas.
+    /// ```
+    /// # use heart437::*;
+    /// # let font = Font::default();
+    /// let mut layer = Layer::new(&font, xy(80, 25), pxy(1, 1), pxy(0, 0));
+    /// let heat = VecGrid::new(xy(20, 10), 0.0f32);
+    /// layer.shade_grid(xy(0, 0), &heat, Some(RED), Some(BLACK));
+    /// ```
+    fn shade_grid<G: Grid<CellType=f32>>(&mut self, pos: Coord, grid: &G, fg: Option<Color>, bg: Option<Color>) where Self: Sized {
+        let finite = || grid.iter().copied().filter(|v| v.is_finite());
+        let min = finite().fold(f32::INFINITY, f32::min);
+        let max = finite().fold(f32::NEG_INFINITY, f32::max);
+
+        for pt in grid.size() {
+            let value = *grid.get(pt).unwrap();
+            if value.is_finite() && self.within(pos + pt) {
+                let level = if max > min { (value - min) / (max - min) } else { 1.0 };
+                self.shade(pos + pt, level, fg, bg)
+            }
+        }
+    }
+
+    /// Draw a horizontal bar `width` cells long, filled from the left by `fraction` (clamped to
+    /// 0..1): full blocks in the `filled` color, then a half block if at least half a cell is
+    /// left over, and the rest blank on the `empty` color. Every cell's background is set too, so
+    /// a label printed over the bar afterward stays readable. Clipped to the region of the
+    /// canvas. Returns how many cells were completely filled.
+    /// ```
+    /// # use heart437::*;
+    /// # let font = Font::default();
+    /// let mut layer = Layer::new(&font, xy(80, 25), pxy(1, 1), pxy(0, 0));
+    /// # let (hp, max_hp) = (13, 20);
+    /// layer.progress_bar(xy(1, 0), 20, hp as f32 / max_hp as f32, RED, Color::rgba(64, 0, 0, 255));
+    /// layer.print(xy(1, 0), &format!("HP {}/{}", hp, max_hp), Some(WHITE), None);
+    /// ```
+    fn progress_bar(&mut self, pos: Coord, width: i32, fraction: f32, filled: Color, empty: Color) -> i32 {
+        let cells = fraction.clamp(0.0, 1.0) * width.max(0) as f32;
+        let full = cells as i32;
+        let half = cells - full as f32 >= 0.5;
+
+        for x in 0..width {
+            let pt = pos + xy(x, 0);
+            if !self.within(pt) { continue }
+            if x < full {
+                self.set_glyph(pt, Some(0xdb), Some(filled), Some(filled))
+            } else if x == full && half {
+                self.set_glyph(pt, Some(0xdd), Some(filled), Some(empty))
+            } else {
+                self.set_glyph(pt, Some(b' '), Some(empty), Some(empty))
+            }
+        }
+        full
+    }
+
+    /// Write a string left-to-right starting at a point. Text past the right edge is cut off rather
+    /// than wrapped, and `\n` moves down a row, back to the starting x.
+    /// ```
+    /// # use heart437::*;
+    /// # let font = Font::default();
+    /// let mut layer = Layer::new(&font, xy(20, 5), pxy(1, 1), pxy(0, 0));
+    /// layer.print(xy(1, 1), "HP: 12\nMP: 4", Some(YELLOW), None);
+    /// ```
+    fn print(&mut self, at: Coord, text: &str, fg: Option<Color>, bg: Option<Color>) {
+        for (row, line) in text.split('\n').enumerate() {
+            for (col, ch) in line.chars().enumerate() {
+                let pt = xy(at.0 + col as i32, at.1 + row as i32);
+                if self.within(pt) {
+                    self.set(pt, Some(ch), fg, bg)
+                }
+            }
+        }
+    }
+
+    /// Write a string into a box, word-wrapping it to the box's width (and breaking up words too
+    /// long to fit) and cutting it off at the box's height. `\n` always starts a new line. Returns
+    /// how many rows of the box were used, which is 0 for empty text.
+    /// ```
+    /// # use heart437::*;
+    /// # let font = Font::default();
+    /// let mut layer = Layer::new(&font, xy(20, 10), pxy(1, 1), pxy(0, 0));
+    /// let rows = layer.print_wrapped(xy(1, 1), xy(10, 5), "You see a rusty iron key here.", Some(WHITE), None);
+    /// assert_eq!(rows, 3);
+    /// ```
+    fn print_wrapped(&mut self, rect_pos: Coord, rect_size: Coord, text: &str, fg: Option<Color>, bg: Option<Color>) -> i32 {
+        if text.is_empty() || rect_size.0 <= 0 || rect_size.1 <= 0 { return 0 }
+        let lines = wrap(text, Some(rect_size.0));
+        let rows = lines.len().min(rect_size.1 as usize);
+        for (row, line) in lines.iter().take(rows).enumerate() {
+            self.print(xy(rect_pos.0, rect_pos.1 + row as i32), line, fg, bg)
+        }
+        rows as i32
+    }
+
+    /// Write a single line of text within a horizontal span of a row, given as `(x, width)`,
+    /// aligned to its left, center, or right. When centering leaves an odd cell over, the extra
+    /// space goes on the right. Text is clipped to the span as well as the canvas.
+    /// ```
+    /// # use heart437::*;
+    /// # let font = Font::default();
+    /// let mut layer = Layer::new(&font, xy(80, 25), pxy(1, 1), pxy(0, 0));
+    /// layer.print_aligned(0, (0, 80), "Main Menu", Align::Center, Some(YELLOW), None);
+    /// ```
+    fn print_aligned(&mut self, line_y: i32, region: (i32, i32), text: &str, align: Align, fg: Option<Color>, bg: Option<Color>) {
+        let (left, width) = region;
+        let len = text.chars().count() as i32;
+        let start = match align {
+            Align::Left => left,
+            Align::Center => left + (width - len).div_euclid(2),
+            Align::Right => left + width - len,
+        };
+        for (n, ch) in text.chars().enumerate() {
+            let pt = xy(start + n as i32, line_y);
+            if pt.0 >= left && pt.0 < left + width && self.within(pt) {
+                self.set(pt, Some(ch), fg, bg)
+            }
+        }
+    }
+
+    /// Paint-bucket fill: set every cell in the 4-connected region around `start` that has the
+    /// same char as `start` does. The region stops at cells with a different char and at the edges
+    /// of the canvas. Only canvases that can be read back, like `Layer`, can be flood filled.
+    /// ```
+    /// # use heart437::*;
+    /// # let font = Font::default();
+    /// let mut layer = Layer::new(&font, xy(20, 10), pxy(1, 1), pxy(0, 0));
+    /// layer.rect(RectStyle::SINGLE.wall(), None, None, xy(2, 2), xy(8, 5));
+    /// layer.flood_fill(xy(4, 4), Some('.'), Some(WHITE), Some(BLUE));
+    /// assert_eq!(layer[xy(4, 4)].ch, b'.');
+    /// assert_eq!(layer[xy(0, 0)].ch, b' ');
+    /// ```
+    fn flood_fill(&mut self, start: Coord, ch: Option<char>, fg: Option<Color>, bg: Option<Color>) where Self: Grid<CellType=Cell> + Sized {
+        let Some(target) = Grid::get(self, start).map(|c| c.ch) else { return };
+        let mut seen = VecGrid::new(Grid::size(self), false);
+        let mut stack = vec![start];
+        seen[start] = true;
+
+        while let Some(pt) = stack.pop() {
+            let next: Vec<Coord> = self.neighbor_coords(pt)
+                .filter(|n| !seen[*n] && Grid::get(self, *n).is_some_and(|c| c.ch == target))
+                .collect();
+            for n in next {
+                seen[n] = true;
+                stack.push(n)
+            }
+            self.set(pt, ch, fg, bg)
+        }
+    }
+
+    /// Turn the single-line box-drawing glyphs in a rectangle into the right corners, tees, and
+    /// crosses for the walls around them, so overlapping or touching `RectStyle::SINGLE` borders
+    /// join up instead of showing stray corners. Each wall keeps the directions it already
+    /// pointed in, and also points toward any neighboring wall that points back at it.
+    /// Neighbors outside the rectangle are looked at but not changed. Only canvases that can be
+    /// read back, like `Layer`, can do this.
+    /// ```
+    /// # use heart437::*;
+    /// # let font = Font::default();
+    /// let mut layer = Layer::new(&font, xy(20, 10), pxy(1, 1), pxy(0, 0));
+    /// let wall = || RectStyle::SINGLE.wall();
+    /// layer.rect(wall(), None, None, xy(0, 0), xy(10, 10));
+    /// layer.rect(wall(), None, None, xy(9, 0), xy(10, 10));
+    /// layer.connect_walls(xy(0, 0), xy(20, 10));
+    /// assert_eq!(layer[xy(9, 0)].ch, 0xc2); // A tee, not the second rect's corner
+    /// ```
+    fn connect_walls(&mut self, pos: Coord, size: Coord) where Self: Grid<CellType=Cell> + Sized {
+        let arms_at = |grid: &Self, pt: Coord| Grid::get(grid, pt).and_then(|c| wall_arms(c.ch));
+
+        let mut pieces = vec![];
+        for pt in Coord::iter_from(pos, pos + size) {
+            let Some((n, s, e, w)) = arms_at(self, pt) else { continue };
+            let points_back = |neighbor: Coord, arm: fn((bool, bool, bool, bool)) -> bool| {
+                arms_at(self, neighbor).is_some_and(arm)
+            };
+            pieces.push((pt, wall_piece(
+                n || points_back(pt.north(), |a| a.1),
+                s || points_back(pt.south(), |a| a.0),
+                e || points_back(pt.east(), |a| a.3),
+                w || points_back(pt.west(), |a| a.2),
+            )))
+        }
+
+        for (pt, piece) in pieces {
+            self.set_glyph(pt, Some(piece), None, None)
+        }
+    }
+
+    /// Draw the outline of a circle by the midpoint circle algorithm, clipped to the region of
+    /// the canvas. A radius of 0 is just the center cell, and a negative radius draws nothing.
+    /// ```
+    /// # use heart437::*;
+    /// # let font = Font::default();
+    /// let mut layer = Layer::new(&font, xy(80, 25), pxy(1, 1), pxy(0, 0));
+    /// # let (player, range) = (xy(40, 12), 6);
+    /// layer.circle(player, range, None, None, Some(Color::rgba(255, 255, 0, 64)));
+    /// ```
+    fn circle(&mut self, center: Coord, radius: i32, ch: Option<char>, fg: Option<Color>, bg: Option<Color>) {
+        for (x, y) in circle_octant(radius) {
+            for (dx, dy) in [(x, y), (y, x), (-y, x), (-x, y), (-x, -y), (-y, -x), (y, -x), (x, -y)] {
+                let pt = center + xy(dx, dy);
+                if self.within(pt) {
+                    self.set(pt, ch, fg, bg)
+                }
+            }
+        }
+    }
+
+    /// Draw a solid disc, with the same edge as `circle` draws, clipped to the region of the
+    /// canvas. A radius of 0 is just the center cell, and a negative radius draws nothing.
+    fn filled_circle(&mut self, center: Coord, radius: i32, ch: Option<char>, fg: Option<Color>, bg: Option<Color>) {
+        for (x, y) in circle_octant(radius) {
+            // Each octant point gives the ends of four rows of the disc:
+            for (half_width, dy) in [(x, y), (x, -y), (y, x), (y, -x)] {
+                for dx in -half_width ..= half_width {
+                    let pt = center + xy(dx, dy);
+                    if self.within(pt) {
+                        self.set(pt, ch, fg, bg)
+                    }
+                }
+            }
+        }
+    }
+
     /// Draw the outline of a rectangle, clipped to the region of the canvas
     /// Rectangles can be drawn in several styles, see `RectStyle`.
     fn rect(&mut self, wall: Wall, fg: Option<Color>, bg: Option<Color>, pos: Coord, size: Coord) {
-        self.set(pos, Some(wall.nw as char), fg, bg);
-        self.set(xy(pos.0 + size.0 - 1, pos.1), Some(wall.ne as char), fg, bg);
-        self.set(xy(pos.0, pos.1 + size.1 - 1), Some(wall.sw as char), fg, bg);
-        self.set(xy(pos.0 + size.0 - 1, pos.1 + size.1 - 1), Some(wall.se as char), fg, bg);
-        for x in (pos.0 + 1) .. (pos.0 + size.0 - 1) {
-            self.set(xy(x, pos.1), Some(wall.n as char), fg, bg);
-            self.set(xy(x, pos.1 + size.1 - 1), Some(wall.s as char), fg, bg);
+        let (right, bottom) = (pos.0 + size.0 - 1, pos.1 + size.1 - 1);
+        let mut border = vec![
+            (pos, wall.nw),
+            (xy(right, pos.1), wall.ne),
+            (xy(pos.0, bottom), wall.sw),
+            (xy(right, bottom), wall.se)
+        ];
+        for x in (pos.0 + 1) .. right {
+            border.push((xy(x, pos.1), wall.n));
+            border.push((xy(x, bottom), wall.s));
         }
 
-        for y in (pos.1 + 1) .. (pos.1 + size.1 - 1) {
-            self.set(xy(pos.0, y), Some(wall.w as char), fg, bg);
-            self.set(xy(pos.0 + size.0 - 1, y), Some(wall.e as char), fg, bg);
+        for y in (pos.1 + 1) .. bottom {
+            border.push((xy(pos.0, y), wall.w));
+            border.push((xy(right, y), wall.e));
+        }
+
+        for (pt, ch) in border {
+            if self.within(pt) {
+                self.set_glyph(pt, Some(ch), fg, bg)
+            }
         }
     }
 }
 
 impl Canvas for Layer<'_> {
+    /// Chars are looked up in the font's `Codepage`; any that aren't in it are drawn as `?`.
     fn set(&mut self, at: Coord, ch: Option<char>, fg: Option<Color>, bg: Option<Color>) {
-        ch.map(|c| self[at] |= Char(c as u8));
-        fg.map(|c| self[at] |= Fg(c));
-        bg.map(|c| self[at] |= Bg(c));
+        let glyph = ch.map(|c| self.font.slot_or_fallback(c));
+        self.set_glyph(at, glyph, fg, bg)
+    }
+
+    fn set_glyph(&mut self, at: Coord, glyph: Option<u8>, fg: Option<Color>, bg: Option<Color>) {
+        Layer::set(self, at, OptionCell { ch: glyph, fg, bg })
     }
 
     fn size(&self) -> Coord {
@@ -70,6 +400,14 @@ impl Canvas for Layer<'_> {
     }
 }
 
+/// How `Canvas::print_aligned` positions text within its span
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
 /// Styles of ASCII rectangles:
 pub enum RectStyle {
     /// Normal rectangles use the +, -, and | characters:
@@ -98,11 +436,33 @@ pub enum RectStyle {
     DOUBLE,
 }
 
-/// The 8 characters (4 sides and 4 corners) making up a rectangle border
+/// The 8 characters (4 sides and 4 corners) making up a rectangle border, as glyph slots in the
+/// font. Get one from a `RectStyle`, or build your own for themed borders.
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Wall {
-    nw: u8, n: u8, ne: u8,
-    w: u8, e: u8,
-    sw: u8, s: u8, se: u8
+    pub nw: u8, pub n: u8, pub ne: u8,
+    pub w: u8, pub e: u8,
+    pub sw: u8, pub s: u8, pub se: u8
+}
+
+impl Wall {
+    /// A border made of any glyphs, given in reading order around the rectangle: the top-left
+    /// corner, top side, and top-right corner; the left and right sides; and the bottom-left
+    /// corner, bottom side, and bottom-right corner.
+    /// ```
+    /// # use heart437::*;
+    /// # let font = Font::default();
+    /// let mut layer = Layer::new(&font, xy(80, 25), pxy(1, 1), pxy(0, 0));
+    /// // A frame of solid and shaded blocks:
+    /// let frame = Wall::custom(0xdb, 0xdf, 0xdb,
+    ///                          0xdb,       0xdb,
+    ///                          0xdb, 0xdc, 0xdb);
+    /// layer.rect(frame, Some(YELLOW), None, xy(10, 5), xy(30, 10));
+    /// ```
+    #[allow(clippy::too_many_arguments)]
+    pub fn custom(nw: u8, n: u8, ne: u8, w: u8, e: u8, sw: u8, s: u8, se: u8) -> Wall {
+        Wall { nw, n, ne, w, e, sw, s, se }
+    }
 }
 
 impl RectStyle {
@@ -155,4 +515,361 @@ impl RectStyle {
             },
         }
     }
-}
\ No newline at end of file
+}
+
+/// The directions `(n, s, e, w)` a code page 437 single-line wall piece connects in, or `None` if
+/// the glyph isn't one. The reverse of `wall_piece`, except the lone square has no arms.
+fn wall_arms(glyph: u8) -> Option<(bool, bool, bool, bool)> {
+    match glyph {
+        0xfe => Some((false, false, false, false)),
+        0xb3 => Some((true, true, false, false)),
+        0xc4 => Some((false, false, true, true)),
+        0xda => Some((false, true, true, false)),
+        0xbf => Some((false, true, false, true)),
+        0xc0 => Some((true, false, true, false)),
+        0xd9 => Some((true, false, false, true)),
+        0xc3 => Some((true, true, true, false)),
+        0xb4 => Some((true, true, false, true)),
+        0xc2 => Some((false, true, true, true)),
+        0xc1 => Some((true, false, true, true)),
+        0xc5 => Some((true, true, true, true)),
+        _ => None
+    }
+}
+
+/// The points of one octant of a circle around the origin, by the midpoint circle algorithm: from
+/// `(radius, 0)` up to the diagonal, with x >= y. Empty for a negative radius.
+fn circle_octant(radius: i32) -> Vec<(i32, i32)> {
+    let (mut x, mut y, mut err) = (radius, 0, 1 - radius);
+    let mut points = vec![];
+    while x >= y {
+        points.push((x, y));
+        y += 1;
+        if err < 0 {
+            err += 2 * y + 1
+        } else {
+            x -= 1;
+            err += 2 * (y - x) + 1
+        }
+    }
+    points
+}
+
+/// The code page 437 single-line wall piece (straight, corner, tee, or cross) that connects to
+/// the given orthogonal neighbors. A wall with no neighbors is a small square, 0xfe.
+pub(crate) fn wall_piece(n: bool, s: bool, e: bool, w: bool) -> u8 {
+    match (n, s, e, w) {
+        (false, false, false, false) => 0xfe,
+        (_, _, false, false) => 0xb3,
+        (false, false, _, _) => 0xc4,
+        (false, true, true, false) => 0xda,
+        (false, true, false, true) => 0xbf,
+        (true, false, true, false) => 0xc0,
+        (true, false, false, true) => 0xd9,
+        (true, true, true, false) => 0xc3,
+        (true, true, false, true) => 0xb4,
+        (false, true, true, true) => 0xc2,
+        (true, false, true, true) => 0xc1,
+        (true, true, true, true) => 0xc5,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{BLACK, Cell, Char, Codepage, Font, pxy, WHITE};
+    use super::*;
+
+    #[test]
+    fn test_rect_clipping() {
+        let font = Font::default();
+        let mut layer = Layer::new(&font, xy(5, 5), pxy(1, 1), pxy(0, 0));
+        layer.rect(RectStyle::NORMAL.wall(), None, None, xy(-1, -1), xy(3, 3));
+
+        // Only the bottom-right part of the box is on the layer:
+        assert_eq!(layer[xy(1, 1)].ch, b'+');
+        assert_eq!(layer[xy(0, 1)].ch, b'-');
+        assert_eq!(layer[xy(1, 0)].ch, b'|');
+        assert_eq!(layer[xy(0, 0)], Cell::default());
+        let set = layer.iter().filter(|c| **c != Cell::default()).count();
+        assert_eq!(set, 3);
+
+        // A box entirely off the layer is fine too:
+        layer.rect(RectStyle::DOUBLE.wall(), None, None, xy(-10, 8), xy(4, 4));
+    }
+
+    #[test]
+    fn test_codepage() {
+        let font = Font::default().with_codepage(Codepage::cp437());
+        let mut layer = Layer::new(&font, xy(5, 5), pxy(1, 1), pxy(0, 0));
+        layer.fill(Some('░'), None, None);
+        assert_eq!(layer[xy(2, 2)].ch, 0xb0);
+
+        // Walls are glyphs, not chars, so they don't go through the codepage:
+        layer.rect(RectStyle::DOUBLE.wall(), None, None, xy(0, 0), xy(5, 5));
+        assert_eq!(layer[xy(0, 0)].ch, RectStyle::DOUBLE.wall().nw);
+    }
+
+    #[test]
+    fn test_char_not_in_codepage() {
+        let font = Font::default();
+        let mut layer = Layer::new(&font, xy(5, 2), pxy(1, 1), pxy(0, 0));
+        layer.print(xy(0, 0), "café", None, None);
+        let text: String = (0..5).map(|x| layer[xy(x, 0)].ch as char).collect();
+        assert_eq!(text, "caf? ");
+        layer.print_wrapped(xy(0, 1), xy(5, 1), "日本", None, None);
+        assert_eq!((layer[xy(0, 1)].ch, layer[xy(1, 1)].ch), (b'?', b'?'));
+
+        // Still something, even if the codepage doesn't have a '?':
+        let font = Font::default().with_codepage(Codepage::empty());
+        let mut layer = Layer::new(&font, xy(1, 1), pxy(1, 1), pxy(0, 0));
+        layer.fill(Some('x'), None, None);
+        assert_eq!(layer[xy(0, 0)].ch, b'?');
+    }
+
+    #[test]
+    fn test_print() {
+        let font = Font::default();
+        let mut layer = Layer::new(&font, xy(5, 3), pxy(1, 1), pxy(0, 0));
+        layer.print(xy(2, 0), "abcdef\ngh\n\nij", None, None);
+        let text: String = layer.iter().map(|c| c.ch as char).collect();
+        assert_eq!(text, "  abc  gh      ");
+
+        // Clipped on the left too:
+        layer.print(xy(-1, 2), "xyz", None, None);
+        assert_eq!(layer[xy(0, 2)].ch, b'y');
+    }
+
+    #[test]
+    fn test_print_wrapped() {
+        let font = Font::default();
+        let mut layer = Layer::new(&font, xy(6, 3), pxy(1, 1), pxy(0, 0));
+        let rows = layer.print_wrapped(xy(1, 0), xy(4, 2), "ab  cd\nefghij", None, None);
+        assert_eq!(rows, 2);
+        let text: String = layer.iter().map(|c| c.ch as char).collect();
+        assert_eq!(text, " ab    cd         ");
+
+        assert_eq!(layer.print_wrapped(xy(0, 0), xy(4, 2), "", None, None), 0);
+        assert_eq!(layer.print_wrapped(xy(0, 0), xy(6, 3), "one\ntwo", None, None), 2);
+    }
+
+    #[test]
+    fn test_fill_gradient() {
+        let font = Font::default();
+        let mut layer = Layer::new(&font, xy(5, 3), pxy(1, 1), pxy(0, 0));
+        layer.fill_gradient(xy(0, 0), xy(5, 3), BLACK, WHITE, true);
+        assert_eq!(layer[xy(0, 2)].bg, BLACK);
+        assert_eq!(layer[xy(2, 1)].bg, Color::rgba(128, 128, 128, 255));
+        assert_eq!(layer[xy(4, 0)].bg, WHITE);
+        assert_eq!(layer[xy(4, 0)].ch, b' ');
+
+        // Clipped, but still spread across the whole (partly offscreen) rectangle:
+        layer.fill_gradient(xy(0, -2), xy(1, 5), BLACK, WHITE, false);
+        assert_eq!(layer[xy(0, 0)].bg, Color::rgba(128, 128, 128, 255));
+        assert_eq!(layer[xy(0, 2)].bg, WHITE);
+    }
+
+    #[test]
+    fn test_print_aligned() {
+        let font = Font::default();
+        let mut layer = Layer::new(&font, xy(8, 1), pxy(1, 1), pxy(0, 0));
+        let text = |layer: &Layer| layer.iter().map(|c| c.ch as char).collect::<String>();
+
+        layer.print_aligned(0, (1, 6), "abc", Align::Left, None, None);
+        assert_eq!(text(&layer), " abc    ");
+        layer.clear();
+        layer.print_aligned(0, (1, 6), "abc", Align::Center, None, None);
+        assert_eq!(text(&layer), "  abc   ");
+        layer.clear();
+        layer.print_aligned(0, (1, 6), "abc", Align::Right, None, None);
+        assert_eq!(text(&layer), "    abc ");
+
+        // Too long for the span, so it's clipped to it:
+        layer.clear();
+        layer.print_aligned(0, (1, 6), "abcdefghij", Align::Center, None, None);
+        assert_eq!(text(&layer), " cdefgh ");
+    }
+
+    #[test]
+    fn test_line() {
+        let font = Font::default();
+        let mut layer = Layer::new(&font, xy(5, 3), pxy(1, 1), pxy(0, 0));
+        layer.line(xy(-1, -1), xy(3, 3), Some('*'), None, None);
+        layer.line(xy(4, 2), xy(4, 0), Some('|'), None, None);
+        let text: String = layer.iter().map(|c| c.ch as char).collect();
+        assert_eq!(text, "*   | *  |  * |");
+    }
+
+    #[test]
+    fn test_circle() {
+        let font = Font::default();
+        let text = |layer: &Layer| layer.iter().map(|c| c.ch as char).collect::<String>();
+        let mut layer = Layer::new(&font, xy(5, 5), pxy(1, 1), pxy(0, 0));
+
+        layer.circle(xy(2, 2), 2, Some('o'), None, None);
+        assert_eq!(text(&layer), " ooo o   oo   oo   o ooo ");
+
+        layer.clear();
+        layer.filled_circle(xy(2, 2), 2, Some('o'), None, None);
+        assert_eq!(text(&layer), " ooo ooooooooooooooo ooo ");
+
+        layer.clear();
+        layer.circle(xy(2, 2), 0, Some('o'), None, None);
+        layer.filled_circle(xy(0, 0), 0, Some('x'), None, None);
+        assert_eq!(text(&layer), "x           o            ");
+
+        layer.clear();
+        layer.circle(xy(2, 2), -1, Some('o'), None, None);
+        layer.filled_circle(xy(2, 2), -3, Some('o'), None, None);
+        assert_eq!(text(&layer), " ".repeat(25));
+
+        // Clipped at the edges:
+        layer.filled_circle(xy(0, 4), 2, Some('o'), None, None);
+        assert_eq!(text(&layer), "          oo   ooo  ooo  ");
+    }
+
+    #[test]
+    fn test_filled_circle_has_no_gaps() {
+        let font = Font::default();
+        let mut layer = Layer::new(&font, xy(41, 41), pxy(1, 1), pxy(0, 0));
+        for radius in 0..=20 {
+            layer.clear();
+            layer.filled_circle(xy(20, 20), radius, Some('o'), None, None);
+            let mut outline = layer.clone();
+            outline.clear();
+            outline.circle(xy(20, 20), radius, Some('o'), None, None);
+
+            for y in 0..41 {
+                let row: Vec<i32> = (0..41).filter(|x| layer[xy(*x, y)].ch == b'o').collect();
+                // Each row of the disc is one unbroken run, which covers the outline's cells:
+                if let (Some(first), Some(last)) = (row.first(), row.last()) {
+                    assert_eq!(row.len() as i32, last - first + 1);
+                }
+                for x in 0..41 {
+                    if outline[xy(x, y)].ch == b'o' { assert_eq!(layer[xy(x, y)].ch, b'o') }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_flood_fill() {
+        let font = Font::default();
+        let text = |layer: &Layer| layer.iter().map(|c| c.ch as char).collect::<String>();
+        let mut layer = Layer::new(&font, xy(5, 4), pxy(1, 1), pxy(0, 0));
+        crate::Canvas::print(&mut layer, xy(0, 0), "  #  \n ### \n#  # \n   # ", None, None);
+
+        // Only orthogonal neighbors are connected, so (0, 2) and the right side are left alone:
+        layer.flood_fill(xy(0, 0), Some('.'), None, Some(WHITE));
+        assert_eq!(text(&layer), "..#  .### #  #    # ");
+        assert_eq!(layer[xy(0, 0)].bg, WHITE);
+        assert_eq!(layer[xy(2, 0)].bg, Cell::default().bg);
+
+        layer.flood_fill(xy(1, 2), Some(' '), None, None);
+        layer.flood_fill(xy(2, 1), Some('#'), None, None); // Filling with the same char stops
+        assert_eq!(text(&layer), "..#  .### #  #    # ");
+
+        layer.flood_fill(xy(-1, 0), Some('x'), None, None); // Off the canvas does nothing
+        layer.flood_fill(xy(3, 1), Some('x'), None, None);
+        assert_eq!(text(&layer), "..x  .xxx #  x    x ");
+    }
+
+    #[test]
+    fn test_flood_fill_large() {
+        // Big enough that a recursive fill would blow the stack
+        let font = Font::default();
+        let mut layer = Layer::new(&font, xy(400, 400), pxy(1, 1), pxy(0, 0));
+        layer.flood_fill(xy(200, 200), Some('~'), None, None);
+        assert!(layer.iter().all(|c| c.ch == b'~'));
+    }
+
+    #[test]
+    fn test_connect_walls() {
+        let font = Font::default();
+        let mut layer = Layer::new(&font, xy(7, 5), pxy(1, 1), pxy(0, 0));
+        layer.rect(RectStyle::SINGLE.wall(), None, None, xy(0, 0), xy(4, 5));
+        layer.rect(RectStyle::SINGLE.wall(), None, None, xy(3, 0), xy(4, 5));
+        layer.rect(RectStyle::SINGLE.wall(), None, None, xy(0, 2), xy(7, 1));
+        layer[xy(5, 3)] |= Char(0xc4);
+        layer.connect_walls(xy(0, 0), xy(7, 5));
+
+        let rows: Vec<Vec<u8>> = (0..5).map(|y| (0..7).map(|x| layer[xy(x, y)].ch).collect()).collect();
+        assert_eq!(rows, vec![
+            vec![0xda, 0xc4, 0xc4, 0xc2, 0xc4, 0xc4, 0xbf],
+            vec![0xb3, b' ', b' ', 0xb3, b' ', b' ', 0xb3],
+            vec![0xc3, 0xc4, 0xc4, 0xc5, 0xc4, 0xc4, 0xb4],
+            vec![0xb3, b' ', b' ', 0xb3, b' ', 0xc4, 0xb4],
+            vec![0xc0, 0xc4, 0xc4, 0xc1, 0xc4, 0xc4, 0xd9],
+        ]);
+
+        // Outside the region, nothing changes:
+        let mut layer = Layer::new(&font, xy(7, 5), pxy(1, 1), pxy(0, 0));
+        layer.rect(RectStyle::SINGLE.wall(), None, None, xy(0, 0), xy(4, 5));
+        layer.rect(RectStyle::SINGLE.wall(), None, None, xy(3, 0), xy(4, 5));
+        layer.connect_walls(xy(0, 0), xy(3, 5));
+        assert_eq!(layer[xy(3, 0)].ch, 0xda);
+    }
+
+    #[test]
+    fn test_custom_wall() {
+        let font = Font::default();
+        let mut layer = Layer::new(&font, xy(3, 3), pxy(1, 1), pxy(0, 0));
+        let wall = Wall::custom(b'1', b'2', b'3', b'4', b'6', b'7', b'8', b'9');
+        layer.rect(wall, None, None, xy(0, 0), xy(3, 3));
+        let text: String = layer.iter().map(|c| c.ch as char).collect();
+        assert_eq!(text, "1234 6789");
+    }
+
+    #[test]
+    fn test_hline_vline() {
+        let font = Font::default();
+        let mut layer = Layer::new(&font, xy(4, 3), pxy(1, 1), pxy(0, 0));
+        layer.hline(xy(-1, 1), 3, Some('-'), None, None);
+        layer.vline(xy(3, 1), 5, Some('|'), None, None);
+        layer.hline(xy(0, 0), -2, Some('x'), None, None);
+        layer.vline(xy(0, 0), 0, Some('x'), None, None);
+        let text: String = layer.iter().map(|c| c.ch as char).collect();
+        assert_eq!(text, "    -- |   |");
+    }
+
+    #[test]
+    fn test_shade() {
+        let font = Font::default();
+        let mut layer = Layer::new(&font, xy(7, 2), pxy(1, 1), pxy(0, 0));
+        for (x, level) in [-1.0, 0.1, 0.2, 0.5, 0.8, 1.0, 2.0].into_iter().enumerate() {
+            layer.shade(xy(x as i32, 0), level, None, None)
+        }
+        let row: Vec<u8> = (0..7).map(|x| layer[xy(x, 0)].ch).collect();
+        assert_eq!(row, vec![b' ', b' ', 0xb0, 0xb1, 0xb2, 0xdb, 0xdb]);
+
+        let mut grid = VecGrid::new(xy(5, 1), 0.0);
+        for x in 0..4 { grid[xy(x, 0)] = 10.0 + x as f32 * 10.0 }
+        grid[xy(4, 0)] = f32::INFINITY;
+        layer.shade_grid(xy(2, 1), &grid, None, None);
+        let row: Vec<u8> = (0..7).map(|x| layer[xy(x, 1)].ch).collect();
+        assert_eq!(row, vec![b' ', b' ', b' ', 0xb0, 0xb2, 0xdb, b' ']);
+    }
+
+    #[test]
+    fn test_progress_bar() {
+        let font = Font::default();
+        let mut layer = Layer::new(&font, xy(6, 1), pxy(1, 1), pxy(0, 0));
+        let chars = |layer: &Layer| layer.iter().map(|c| c.ch).collect::<Vec<u8>>();
+
+        assert_eq!(layer.progress_bar(xy(1, 0), 4, 0.65, WHITE, BLACK), 2);
+        assert_eq!(chars(&layer), vec![b' ', 0xdb, 0xdb, 0xdd, b' ', b' ']);
+        assert_eq!((layer[xy(3, 0)].fg, layer[xy(3, 0)].bg), (WHITE, BLACK));
+        assert_eq!(layer[xy(4, 0)].bg, BLACK);
+        assert_eq!(layer[xy(5, 0)].bg, Cell::default().bg);
+
+        // Less than half a cell over, and clamped:
+        assert_eq!(layer.progress_bar(xy(1, 0), 4, 0.6, WHITE, BLACK), 2);
+        assert_eq!(chars(&layer), vec![b' ', 0xdb, 0xdb, b' ', b' ', b' ']);
+        assert_eq!(layer.progress_bar(xy(0, 0), 6, 1.5, WHITE, BLACK), 6);
+        assert_eq!(layer.progress_bar(xy(0, 0), 6, -1.0, WHITE, BLACK), 0);
+        assert_eq!(chars(&layer), vec![b' '; 6]);
+
+        // Clipped, but still counts the whole bar:
+        assert_eq!(layer.progress_bar(xy(4, 0), 10, 0.5, WHITE, BLACK), 5);
+        assert_eq!(chars(&layer), vec![b' ', b' ', b' ', b' ', 0xdb, 0xdb]);
+    }
+}
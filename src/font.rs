@@ -1,10 +1,82 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::ops::{Bound, RangeBounds};
+use std::sync::{Arc, Mutex};
 use image::{DynamicImage, GenericImageView};
+use crate::{Coord, xy};
 
 /// A set of 256 glyphs, 8x8 pixels in size, which can be rendered to a `Layer` in a foreground
 /// and background color.
 #[derive(Copy, Clone, Debug)]
 pub struct Font {
-    glyphs: [Glyph; 256]
+    glyphs: [Glyph; 256],
+    codepage: Codepage
+}
+
+/// Which glyph slot in a `Font` each char is drawn with. Each of the 256 slots shows at most one
+/// char, and a char not in any slot can't be drawn. The default, `Codepage::ascii`, has ASCII
+/// chars in the slots matching their codes, and nothing else.
+/// ```
+/// # use heart437::*;
+/// let font = Font::default().with_codepage(Codepage::cp437());
+/// assert_eq!(font.codepage().slot('█'), Some(0xdb));
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Codepage([Option<char>; 256]);
+
+impl Default for Codepage {
+    fn default() -> Self {
+        Self::ascii()
+    }
+}
+
+impl Codepage {
+    /// A codepage with nothing in it, to fill in with `with`
+    pub fn empty() -> Self {
+        Self([None; 256])
+    }
+
+    /// ASCII chars in the slots matching their codes, and nothing in slots 128..255
+    pub fn ascii() -> Self {
+        let mut slots = [None; 256];
+        for (n, slot) in slots.iter_mut().enumerate().take(128) { *slot = Some(n as u8 as char) }
+        Self(slots)
+    }
+
+    /// Chars up to U+00FF in the slots matching their codes, for fonts laid out like Latin-1
+    pub fn latin1() -> Self {
+        let mut slots = [None; 256];
+        for (n, slot) in slots.iter_mut().enumerate() { *slot = Some(n as u8 as char) }
+        Self(slots)
+    }
+
+    /// The Unicode equivalents of each CP437 glyph (see `Char::to_cp437_char`), for fonts laid
+    /// out like the default one; this lets you draw `'╔'` or `'░'` straight from a string.
+    pub fn cp437() -> Self {
+        Self(crate::cell::CP437.map(Some))
+    }
+
+    /// Put a char in a slot, replacing whatever was there and removing the char from any other slot
+    /// ```
+    /// # use heart437::*;
+    /// let codepage = Codepage::ascii().with('☺', 1).with('♥', 3);
+    /// assert_eq!(codepage.slot('♥'), Some(3));
+    /// ```
+    pub fn with(mut self, ch: char, slot: u8) -> Self {
+        for s in self.0.iter_mut().filter(|s| **s == Some(ch)) { *s = None }
+        self.0[slot as usize] = Some(ch);
+        self
+    }
+
+    /// Which slot a char is in, if any
+    pub fn slot(&self, ch: char) -> Option<u8> {
+        self.0.iter().position(|s| *s == Some(ch)).map(|n| n as u8)
+    }
+
+    /// Which char is in a slot, if any
+    pub fn char_at(&self, slot: u8) -> Option<char> {
+        self.0[slot as usize]
+    }
 }
 
 /// A single symbol in a `Font`, 8x8 pixels in size
@@ -32,6 +104,140 @@ impl Glyph {
         }
         Self(bytes)
     }
+
+    /// How many pixels wide this glyph looks: the width of the columns it actually uses, plus one
+    /// for spacing. Blank glyphs (like space) have a fixed advance of 4.
+    /// ```
+    /// # use heart437::*;
+    /// let font = Font::default();
+    /// assert!(font['i'].advance() < font['W'].advance());
+    /// ```
+    pub fn advance(&self) -> usize {
+        match self.used_cols() {
+            Some((first, last)) => last - first + 2,
+            None => 4
+        }
+    }
+
+    /// Build a glyph from eight rows of ASCII art, where a space is an off pixel and anything
+    /// else is on. Rows shorter than 8 chars are padded with off pixels on the right. Panics if
+    /// there aren't exactly 8 rows, or a row is longer than 8 chars!
+    /// ```
+    /// # use heart437::*;
+    /// let heart = Glyph::from_art(&[
+    ///     " ## ##  ",
+    ///     "####### ",
+    ///     "####### ",
+    ///     "####### ",
+    ///     " #####  ",
+    ///     "  ###   ",
+    ///     "   #    ",
+    ///     "",
+    /// ]);
+    /// assert!(heart.get_pixel(3, 6));
+    /// ```
+    pub fn from_art(rows: &[&str]) -> Glyph {
+        assert_eq!(rows.len(), 8, "Glyph art must have 8 rows!");
+        let mut glyph = Glyph::default();
+        for (y, row) in rows.iter().enumerate() {
+            assert!(row.chars().count() <= 8, "Glyph art rows must be at most 8 chars!");
+            for (x, ch) in row.chars().enumerate() {
+                glyph.set_pixel(x, y, ch != ' ')
+            }
+        }
+        glyph
+    }
+
+    /// Whether the pixel at (x, y) is on (drawn in the foreground color), with (0, 0) at the top
+    /// left. Panics if x or y is outside 0..8!
+    pub fn get_pixel(&self, x: usize, y: usize) -> bool {
+        assert!(x < 8 && y < 8, "Glyph pixels are 0..8 in each direction!");
+        self.0[y] & (1 << (7 - x)) != 0
+    }
+
+    /// Turn the pixel at (x, y) on or off, for animating or editing glyphs at runtime. Panics if
+    /// x or y is outside 0..8!
+    /// ```
+    /// # use heart437::*;
+    /// let mut font = Font::default();
+    /// font['*'].set_pixel(3, 0, true);
+    /// assert!(font['*'].get_pixel(3, 0));
+    /// ```
+    pub fn set_pixel(&mut self, x: usize, y: usize, on: bool) {
+        assert!(x < 8 && y < 8, "Glyph pixels are 0..8 in each direction!");
+        if on {
+            self.0[y] |= 1 << (7 - x)
+        } else {
+            self.0[y] &= !(1 << (7 - x))
+        }
+    }
+
+    /// Whether no pixels in this glyph are on, so it draws as nothing but background
+    pub fn is_blank(&self) -> bool {
+        self.0 == [0; 8]
+    }
+
+    /// How many pixels in this glyph are on, 0..64; a rough measure of how bright it looks
+    /// ```
+    /// # use heart437::*;
+    /// let font = Font::default();
+    /// assert!(font['.'].density() < font['#'].density());
+    /// assert_eq!(font[0xdb].density(), 64); // Full block
+    /// ```
+    pub fn density(&self) -> u32 {
+        self.0.iter().map(|row| row.count_ones()).sum()
+    }
+
+    /// This glyph with every pixel also drawn one to its right, for bold text
+    pub(crate) fn bold(&self) -> Glyph {
+        Glyph(self.0.map(|row| row | row >> 1))
+    }
+
+    /// This glyph with its bottom row turned on
+    pub(crate) fn underlined(&self) -> Glyph {
+        let mut rows = self.0;
+        rows[7] = 0xff;
+        Glyph(rows)
+    }
+
+    /// The first and last columns (0..7) with any pixels on, or `None` for a blank glyph
+    pub(crate) fn used_cols(&self) -> Option<(usize, usize)> {
+        let cols = self.0.iter().fold(0u8, |a, row| a | row);
+        if cols == 0 {
+            None
+        } else {
+            Some((cols.leading_zeros() as usize, 7 - cols.trailing_zeros() as usize))
+        }
+    }
+}
+
+/// Glyph bitmaps expanded to a given scale, each built the first time it's asked for, so that
+/// drawing a cell doesn't have to work out the scaling of every pixel again. Keyed by the glyph's
+/// pixels rather than its slot, so editing a font's glyphs never leaves a stale entry. The map
+/// is behind a `Mutex` so a `Layer` can still be drawn from several threads at once.
+#[derive(Default)]
+pub(crate) struct ScaledGlyphs(Mutex<HashMap<ScaledGlyphKey, Arc<[bool]>>>);
+
+/// A glyph's pixels and the x and y scale it's expanded to
+type ScaledGlyphKey = ([u8; 8], i32, i32);
+
+impl ScaledGlyphs {
+    /// The pixels of a glyph at some scale, as rows `8 * xscale` wide, top to bottom; true
+    /// pixels are foreground.
+    pub(crate) fn get(&self, glyph: Glyph, xscale: i32, yscale: i32) -> Arc<[bool]> {
+        self.0.lock().unwrap().entry((glyph.0, xscale, yscale)).or_insert_with(|| {
+            let (width, height) = (8 * xscale as usize, 8 * yscale as usize);
+            (0..width * height).map(|n| {
+                glyph.get_pixel(n % width / xscale as usize, n / width / yscale as usize)
+            }).collect()
+        }).clone()
+    }
+}
+
+impl Clone for ScaledGlyphs {
+    fn clone(&self) -> Self {
+        Self(Mutex::new(self.0.lock().unwrap().clone()))
+    }
 }
 
 /// An iterator over each pixel in a `Glyph`
@@ -46,9 +252,7 @@ impl Iterator for GlyphIterator<'_> {
             None
         } else {
             let (x, y) = (n % 8, n / 8);
-            let b = self.0.0[y];
-            let color = (b & (1 << (7 - x))) != 0;
-            Some((color, x, y))
+            Some((self.0.get_pixel(x, y), x, y))
         }
     }
 }
@@ -99,7 +303,7 @@ impl Default for Font {
     /// this is) should be free to redistribute and use. (TrueType / vector fonts are a different
     /// story though)
     fn default() -> Self {
-        Self::from_png(include_bytes!("font.png"))
+        Self::from_png(include_bytes!("font.png")).expect("The built-in font should always load")
     }
 }
 
@@ -110,21 +314,190 @@ impl Font {
     /// The image must be a transparent PNG; any pixel with 0 for alpha is taken to be background,
     /// anything non-zero alpha is foreground.
     /// ```
-    /// let font = heart437::Font::from_png(include_bytes!("font.png"));
+    /// let font = heart437::Font::from_png(include_bytes!("font.png")).unwrap();
     /// ```
-    pub fn from_png(image_data: &[u8]) -> Self {
-        let image = image::load_from_memory_with_format(image_data, image::ImageFormat::Png).unwrap();
+    pub fn from_png(image_data: &[u8]) -> Result<Self, FontError> {
+        let image = image::load_from_memory_with_format(image_data, image::ImageFormat::Png)
+            .map_err(|_| FontError::BadImage)?;
         let w = image.width() / 8;
+        if w == 0 || w * (image.height() / 8) < 256 {
+            return Err(FontError::TooSmall)
+        }
         let mut glyphs = [Glyph::default(); 256];
         for n in 0..256 {
             let (x, y) = (n % w, n / w);
             glyphs[n as usize] = Glyph::from_image_slice(&image, x as u32, y as u32);
         }
 
-        Self { glyphs }
+        Ok(Self { glyphs, codepage: Codepage::default() })
+    }
+
+    /// Load a Linux console font in PSF1 or PSF2 format. Only the first 256 glyphs are used.
+    /// Glyphs that aren't 8x8 are scaled to fit: each pixel of the new glyph is on if any pixel in
+    /// the part of the original it covers is, so thin lines survive squeezing a tall glyph (like
+    /// the common 8x16), and narrow ones (like 6x12) are stretched without gaps.
+    pub fn from_psf(data: &[u8]) -> Result<Self, FontError> {
+        let (count, width, height, header_len) = if data.starts_with(&[0x36, 0x04]) && data.len() >= 4 {
+            let count = if data[2] & 1 != 0 { 512 } else { 256 };
+            (count, 8, data[3] as usize, 4)
+        } else if data.starts_with(&[0x72, 0xb5, 0x4a, 0x86]) && data.len() >= 32 {
+            let field = |n: usize| u32::from_le_bytes(data[n * 4 .. n * 4 + 4].try_into().unwrap()) as usize;
+            (field(4), field(7), field(6), field(2))
+        } else {
+            return Err(FontError::BadPsf)
+        };
+
+        if count < 256 { return Err(FontError::TooSmall) }
+        if width == 0 || height == 0 { return Err(FontError::BadPsf) }
+        let row_len = width.div_ceil(8);
+        // These come from the header, so a broken (or malicious) one can overflow:
+        let glyph_len = row_len.checked_mul(height).ok_or(FontError::BadPsf)?;
+        let end = glyph_len.checked_mul(256).and_then(|len| len.checked_add(header_len)).ok_or(FontError::BadPsf)?;
+        let bitmaps = data.get(header_len .. end).ok_or(FontError::BadPsf)?;
+
+        // The pixels of the original that pixel n of the new glyph covers; when the original is
+        // less than 8 wide (or tall), that's the single pixel nearest it.
+        let span = |n: usize, len: usize| {
+            let (start, end) = ((n * len).div_ceil(8), ((n + 1) * len).div_ceil(8));
+            if start < end { start..end } else { n * len / 8 .. n * len / 8 + 1 }
+        };
+
+        let mut glyphs = [Glyph::default(); 256];
+        for (glyph, bitmap) in glyphs.iter_mut().zip(bitmaps.chunks(glyph_len)) {
+            let on = |x: usize, y: usize| bitmap[y * row_len + x / 8] & (0x80 >> (x % 8)) != 0;
+            for (gx, gy) in (0..64).map(|n| (n % 8, n / 8)) {
+                if span(gy, height).any(|y| span(gx, width).any(|x| on(x, y))) {
+                    glyph.set_pixel(gx, gy, true)
+                }
+            }
+        }
+
+        Ok(Self { glyphs, codepage: Codepage::default() })
+    }
+
+    /// This font, using a different `Codepage` to look up chars
+    pub fn with_codepage(self, codepage: Codepage) -> Self {
+        Self { codepage, ..self }
+    }
+
+    /// The `Codepage` this font looks up chars with
+    pub fn codepage(&self) -> &Codepage {
+        &self.codepage
+    }
+
+    /// The slot a char is drawn from in this font. Panics if the codepage doesn't have the char!
+    pub(crate) fn slot(&self, ch: char) -> u8 {
+        match self.codepage.slot(ch) {
+            Some(slot) => slot,
+            None => panic!("{:?} isn't in this font's codepage!", ch)
+        }
+    }
+
+    /// The slot a char is drawn from in this font, or the slot of `?` if the codepage doesn't have
+    /// it (slot 63, where ASCII keeps `?`, if it doesn't have that either). For drawing text,
+    /// where a wrong glyph is better than a panic.
+    pub(crate) fn slot_or_fallback(&self, ch: char) -> u8 {
+        self.codepage.slot(ch).or(self.codepage.slot('?')).unwrap_or(b'?')
+    }
+
+    /// Copy a range of glyphs from another font into this one, replacing the glyphs in the same
+    /// slots. Handy for building a tileset from the default font plus a few custom glyphs.
+    /// Any kind of range works, so `200..=255` can reach the last slot.
+    /// ```
+    /// # use heart437::*;
+    /// # let custom = Font::default();
+    /// let mut font = Font::default();
+    /// font.overlay(&custom, 1..3); // Replace the two smiley faces
+    /// ```
+    pub fn overlay<R: RangeBounds<u8>>(&mut self, other: &Font, range: R) {
+        let start = match range.start_bound() {
+            Bound::Included(n) => *n as usize,
+            Bound::Excluded(n) => *n as usize + 1,
+            Bound::Unbounded => 0
+        };
+        let end = match range.end_bound() {
+            Bound::Included(n) => *n as usize + 1,
+            Bound::Excluded(n) => *n as usize,
+            Bound::Unbounded => 256
+        };
+        if start < end {
+            self.glyphs[start..end].copy_from_slice(&other.glyphs[start..end])
+        }
+    }
+
+    /// How many cells wide and tall some text is when printed, for sizing a box to fit it.
+    /// `\n` always starts a new line, and if there's a `max_width`, lines longer than that are
+    /// word-wrapped (and words longer than that are broken up). Empty text is 0 by 0.
+    /// ```
+    /// # use heart437::*;
+    /// let font = Font::default();
+    /// assert_eq!(font.measure_text("Hello\nworld!", None), xy(6, 2));
+    /// assert_eq!(font.measure_text("The quick brown fox", Some(10)), xy(9, 2));
+    /// ```
+    pub fn measure_text(&self, text: &str, max_width: Option<i32>) -> Coord {
+        if text.is_empty() { return xy(0, 0) }
+        let lines = wrap(text, max_width);
+        let width = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+        xy(width as i32, lines.len() as i32)
     }
 }
 
+/// Split text into the lines it prints as: breaking at every `\n`, and word-wrapping any line
+/// longer than `max_width` (if there is one), breaking words that are too long by themselves.
+/// Spaces between words are kept, except where a line wraps.
+pub(crate) fn wrap(text: &str, max_width: Option<i32>) -> Vec<String> {
+    let mut lines = vec![];
+    for line in text.split('\n') {
+        match max_width {
+            Some(w) if w > 0 && line.chars().count() > w as usize => {
+                let w = w as usize;
+                let mut current: Vec<char> = vec![];
+                let mut gap = 0;
+                for (n, word) in line.split(' ').enumerate() {
+                    if n > 0 { gap += 1 }
+                    if word.is_empty() { continue }
+                    let mut word: Vec<char> = word.chars().collect();
+                    if current.len() + gap + word.len() <= w {
+                        current.resize(current.len() + gap, ' ');
+                        current.append(&mut word);
+                    } else {
+                        if !current.is_empty() { lines.push(current.drain(..).collect()) }
+                        while word.len() > w { lines.push(word.drain(..w).collect()) }
+                        current = word
+                    }
+                    gap = 0
+                }
+                lines.push(current.into_iter().collect())
+            }
+            _ => lines.push(line.to_string())
+        }
+    }
+    lines
+}
+
+/// The ways loading a `Font` can fail
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FontError {
+    /// The data couldn't be decoded as an image of the expected format
+    BadImage,
+    /// The image decoded fine, but doesn't have room for 256 glyphs (or the font has fewer)
+    TooSmall,
+    /// The data isn't a PSF1 or PSF2 font, or is cut off
+    BadPsf,
+}
+
+impl Display for FontError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FontError::BadImage => write!(f, "Couldn't decode font image"),
+            FontError::TooSmall => write!(f, "Font doesn't have 256 glyphs"),
+            FontError::BadPsf => write!(f, "Couldn't read PSF font"),
+        }
+    }
+}
+
+impl std::error::Error for FontError {}
+
 impl std::ops::IndexMut<u8> for Font {
     /// Fetch the `Glyph` corresponding to a given u8 in this font
     fn index_mut(&mut self, index: u8) -> &mut Self::Output {
@@ -142,21 +515,250 @@ impl std::ops::Index<u8> for Font {
 }
 
 impl std::ops::IndexMut<char> for Font {
-    /// Fetch the `Glyph` corresponding to a given char in this font. Fonts are only defined for
-    /// ASCII chars; will panic if passed a non-ASCII char!
+    /// Fetch the `Glyph` corresponding to a given char in this font, using its `Codepage`. The
+    /// default codepage only has ASCII chars; will panic if passed a char not in the codepage!
     fn index_mut(&mut self, index: char) -> &mut Self::Output {
-        assert!(index.is_ascii(), "Fonts are only defined for ASCII chars!");
-        &mut self.glyphs[index as usize]
+        let slot = self.slot(index);
+        &mut self.glyphs[slot as usize]
     }
 }
 
 impl std::ops::Index<char> for Font {
     type Output = Glyph;
 
-    /// Fetch the `Glyph` corresponding to a given char in this font. Fonts are only defined for
-    /// ASCII chars; will panic if passed a non-ASCII char!
+    /// Fetch the `Glyph` corresponding to a given char in this font, using its `Codepage`. The
+    /// default codepage only has ASCII chars; will panic if passed a char not in the codepage!
     fn index(&self, index: char) -> &Self::Output {
-        assert!(index.is_ascii(), "Fonts are only defined for ASCII chars!");
-        &self.glyphs[index as usize]
+        &self.glyphs[self.slot(index) as usize]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A valid, fully transparent 16x8 PNG: room for only two glyphs
+    const TINY_PNG: [u8; 72] = [
+        0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44, 0x52,
+        0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x08, 0x08, 0x06, 0x00, 0x00, 0x00, 0xf0, 0x76, 0x7f,
+        0x97, 0x00, 0x00, 0x00, 0x0f, 0x49, 0x44, 0x41, 0x54, 0x78, 0xda, 0x63, 0x60, 0x18, 0x05, 0xa3,
+        0x80, 0x81, 0x01, 0x00, 0x02, 0x08, 0x00, 0x01, 0x50, 0x31, 0x49, 0x80, 0x00, 0x00, 0x00, 0x00,
+        0x49, 0x45, 0x4e, 0x44, 0xae, 0x42, 0x60, 0x82
+    ];
+
+    #[test]
+    fn test_from_png() {
+        assert!(Font::from_png(include_bytes!("font.png")).is_ok());
+        assert_eq!(Font::from_png(b"not a png").unwrap_err(), FontError::BadImage);
+        assert_eq!(Font::from_png(&TINY_PNG).unwrap_err(), FontError::TooSmall);
+    }
+
+    #[test]
+    fn test_overlay() {
+        let mut blank = Font::default();
+        for n in 0..=255u8 { blank[n] = Glyph::default() }
+        let full = Font::default();
+        let blank_slots = |font: &Font| (0..=255u8).filter(|n| font[*n].0 == [0; 8]).collect::<Vec<u8>>();
+
+        let mut font = full;
+        font.overlay(&blank, 65..68);
+        assert_eq!(font[b'A'].0, [0; 8]);
+        assert_eq!(font[b'D'].0, full[b'D'].0);
+
+        let mut font = blank;
+        font.overlay(&full, 250..=255);
+        assert_eq!(font[255].0, full[255].0);
+        assert_eq!(font[249].0, [0; 8]);
+
+        let mut font = blank;
+        font.overlay(&full, ..);
+        assert_eq!(blank_slots(&font), blank_slots(&full));
+
+        let mut font = full;
+        font.overlay(&blank, 10..10);
+        assert_eq!(blank_slots(&font), blank_slots(&full));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_pixels() {
+        let mut glyph = Glyph::from([0b10000000, 0, 0, 0, 0, 0, 0, 0b00000001]);
+        assert!(glyph.get_pixel(0, 0) && glyph.get_pixel(7, 7));
+        assert!(!glyph.get_pixel(1, 0) && !glyph.get_pixel(7, 6));
+
+        glyph.set_pixel(0, 0, false);
+        glyph.set_pixel(2, 3, true);
+        glyph.set_pixel(2, 3, true);
+        assert_eq!(glyph.0, [0, 0, 0, 0b00100000, 0, 0, 0, 0b00000001]);
+        assert_eq!((&glyph).into_iter().filter(|(on, _, _)| *on).count(), 2);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_pixel_bounds() {
+        Glyph::default().set_pixel(8, 0, true);
+    }
+
+    #[test]
+    fn test_from_art() {
+        let glyph = Glyph::from_art(&["#      #", " ██", "", "", "", "", "", "########"]);
+        assert_eq!(glyph.0, [0b10000001, 0b01100000, 0, 0, 0, 0, 0, 0b11111111]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_from_art_rows() {
+        Glyph::from_art(&["#"; 7]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_from_art_width() {
+        Glyph::from_art(&["", "", "", "#########", "", "", "", ""]);
+    }
+
+    #[test]
+    fn test_wrap() {
+        assert_eq!(wrap("one two  three", None), vec!["one two  three"]);
+        assert_eq!(wrap("one two  three", Some(14)), vec!["one two  three"]);
+        assert_eq!(wrap("one two  three", Some(7)), vec!["one two", "three"]);
+        assert_eq!(wrap("a\n\nb", Some(7)), vec!["a", "", "b"]);
+        assert_eq!(wrap("abcdefghij xy", Some(4)), vec!["abcd", "efgh", "ij", "xy"]);
+        assert_eq!(wrap("ab abcdefghij", Some(4)), vec!["ab", "abcd", "efgh", "ij"]);
+        assert_eq!(wrap("a  b   cc", Some(5)), vec!["a  b", "cc"]);
+        assert_eq!(wrap("  indented text", Some(11)), vec!["  indented", "text"]);
+    }
+
+    #[test]
+    fn test_measure_text() {
+        let font = Font::default();
+        assert_eq!(font.measure_text("", Some(5)), xy(0, 0));
+        assert_eq!(font.measure_text("Hello", None), xy(5, 1));
+        assert_eq!(font.measure_text("Hello\n", None), xy(5, 2));
+        assert_eq!(font.measure_text("Hello there friend", Some(11)), xy(11, 2));
+    }
+
+    #[test]
+    fn test_from_psf1() {
+        // 8x8, 256 glyphs, glyph 1 has a dot in its top left corner:
+        let mut data = vec![0x36, 0x04, 0, 8];
+        data.extend_from_slice(&[0; 256 * 8]);
+        data[4 + 8] = 0x80;
+        let font = Font::from_psf(&data).unwrap();
+        assert_eq!(font[1].0, [0x80, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(font[0].0, [0; 8]);
+
+        assert_eq!(Font::from_psf(&data[..100]).unwrap_err(), FontError::BadPsf);
+        assert_eq!(Font::from_psf(b"nope").unwrap_err(), FontError::BadPsf);
+    }
+
+    #[test]
+    fn test_from_psf2() {
+        // 8x16, 256 glyphs, glyph 65 has a line on its last row:
+        let mut data = vec![0x72, 0xb5, 0x4a, 0x86];
+        for field in [0u32, 32, 0, 256, 16, 16, 8] {
+            data.extend_from_slice(&field.to_le_bytes())
+        }
+        data.extend_from_slice(&[0; 256 * 16]);
+        data[32 + 65 * 16 + 15] = 0xff;
+        let font = Font::from_psf(&data).unwrap();
+        assert_eq!(font[65].0, [0, 0, 0, 0, 0, 0, 0, 0xff]);
+
+        // Too few glyphs:
+        data[16..20].copy_from_slice(&100u32.to_le_bytes());
+        assert_eq!(Font::from_psf(&data).unwrap_err(), FontError::TooSmall);
+    }
+
+    #[test]
+    fn test_from_psf2_narrow() {
+        // 6x8, 256 glyphs: glyph 1 has a full top row, glyph 2 a full left column and a dot in
+        // its bottom-right corner:
+        let mut data = vec![0x72, 0xb5, 0x4a, 0x86];
+        for field in [0u32, 32, 0, 256, 8, 8, 6] {
+            data.extend_from_slice(&field.to_le_bytes())
+        }
+        data.extend_from_slice(&[0; 256 * 8]);
+        data[32 + 8] = 0b11111100;
+        for y in 0..8 { data[32 + 16 + y] = 0b10000000 }
+        data[32 + 16 + 7] = 0b10000100;
+        let font = Font::from_psf(&data).unwrap();
+
+        // Stretched across all 8 columns, with no gaps:
+        assert_eq!(font[1].0, [0xff, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(font[2].0, [0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x83]);
+    }
+
+    #[test]
+    fn test_from_psf2_oversized() {
+        let header = |header_len: u32, height: u32, width: u32| {
+            let mut data = vec![0x72, 0xb5, 0x4a, 0x86];
+            for field in [0u32, header_len, 0, 256, 16, height, width] {
+                data.extend_from_slice(&field.to_le_bytes())
+            }
+            data.extend_from_slice(&[0; 256 * 16]);
+            data
+        };
+
+        // Glyphs too big to even compute the size of:
+        assert_eq!(Font::from_psf(&header(32, u32::MAX, u32::MAX)).unwrap_err(), FontError::BadPsf);
+        // Or just much bigger than the data:
+        assert_eq!(Font::from_psf(&header(u32::MAX, 16, 8)).unwrap_err(), FontError::BadPsf);
+        assert_eq!(Font::from_psf(&header(32, 1, u32::MAX)).unwrap_err(), FontError::BadPsf);
+    }
+
+    #[test]
+    fn test_codepage() {
+        let ascii = Codepage::default();
+        assert_eq!(ascii.slot('A'), Some(65));
+        assert_eq!(ascii.slot('é'), None);
+        assert_eq!(ascii.char_at(200), None);
+        assert_eq!(Codepage::latin1().slot('é'), Some(0xe9));
+        assert_eq!(Codepage::cp437().slot('é'), Some(0x82));
+        assert_eq!(Codepage::cp437().char_at(1), Some('☺'));
+
+        let custom = Codepage::empty().with('@', 1).with('#', 2).with('@', 3);
+        assert_eq!(custom.slot('@'), Some(3));
+        assert_eq!(custom.char_at(1), None);
+        let custom = custom.with('*', 2);
+        assert_eq!(custom.slot('#'), None);
+    }
+
+    #[test]
+    fn test_font_codepage() {
+        let font = Font::default();
+        let cp437 = font.with_codepage(Codepage::cp437());
+        assert_eq!(cp437['█'].0, font[0xdb].0);
+        assert_eq!(cp437['A'].0, font['A'].0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_char_not_in_codepage() {
+        let _ = Font::default()['█'];
+    }
+
+    #[test]
+    fn test_density() {
+        assert!(Glyph::default().is_blank());
+        assert_eq!(Glyph::default().density(), 0);
+        let glyph = Glyph::from([0b10000001, 0, 0, 0, 0, 0, 0, 0b11111111]);
+        assert!(!glyph.is_blank());
+        assert_eq!(glyph.density(), 10);
+        assert!(Font::default()[' '].is_blank());
+    }
+
+    #[test]
+    fn test_scaled_glyphs() {
+        let cache = ScaledGlyphs::default();
+        let glyph = Glyph::from([0b10000001, 0, 0, 0, 0, 0, 0, 0b01000000]);
+        let pixels = cache.get(glyph, 2, 3);
+        assert_eq!(pixels.len(), 16 * 24);
+        let on: Vec<usize> = (0..pixels.len()).filter(|n| pixels[*n]).collect();
+        let expected: Vec<usize> = [0, 1, 14, 15].iter()
+            .flat_map(|x| (0..3).map(move |y| y * 16 + x))
+            .chain((21..24).flat_map(|y| [y * 16 + 2, y * 16 + 3]))
+            .collect::<std::collections::BTreeSet<_>>().into_iter().collect();
+        assert_eq!(on, expected);
+        // The second lookup is the same bitmap, not a new one:
+        assert!(Arc::ptr_eq(&pixels, &cache.get(glyph, 2, 3)));
+    }
+}
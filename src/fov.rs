@@ -1,5 +1,6 @@
-use crate::{Coord, Grid, VecGrid, xy};
+use crate::{Coord, Grid, GridMut, VecGrid, xy};
 use doryen_fov::{FovAlgorithm, FovRecursiveShadowCasting, MapData};
+use line_drawing::WalkGrid;
 
 pub fn shadowcast<G: Grid<CellType=bool>>(grid: G, loc: Coord, radius: u32) -> VecGrid<bool> {
     let mut map_data = grid.mapdata();
@@ -11,6 +12,177 @@ pub fn shadowcast<G: Grid<CellType=bool>>(grid: G, loc: Coord, radius: u32) -> V
     map_data.into()
 }
 
+/// Options for `shadowcast_with_opts`, built like a `CellularMap`:
+/// ```
+/// # use heart437::*;
+/// let opts = FovOpts::new().with_diagonal_blocking(true);
+/// ```
+#[derive(Copy, Clone, Debug, Default)]
+pub struct FovOpts {
+    diagonal_blocking: bool
+}
+
+impl FovOpts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether two walls touching at the corners block sight between them, like this:
+    /// ```text
+    ///   @#
+    ///   #X
+    /// ```
+    /// With this false (the default, and how `shadowcast` behaves) the `@` can see the `X`.
+    pub fn with_diagonal_blocking(mut self, diagonal_blocking: bool) -> Self {
+        self.diagonal_blocking = diagonal_blocking;
+        self
+    }
+}
+
+/// Just like `shadowcast`, but with some extra options; see `FovOpts`.
+pub fn shadowcast_with_opts<G: Grid<CellType=bool>>(grid: G, loc: Coord, radius: u32, opts: FovOpts) -> VecGrid<bool> {
+    let transparent = VecGrid::from_vec(grid.iter().cloned().collect(), grid.size().0 as usize, false);
+    let mut visible = shadowcast(transparent.clone(), loc, radius);
+    if opts.diagonal_blocking { block_diagonals(&transparent, &mut visible, loc) }
+    visible
+}
+
+/// Hide any cells that could only be seen by squeezing between two walls that touch diagonally.
+/// Nearer cells go first: each visible cell has to be reachable from a visible, transparent cell
+/// one step closer to the viewer, and a diagonal step can't pass between two walls.
+fn block_diagonals(transparent: &VecGrid<bool>, visible: &mut VecGrid<bool>, loc: Coord) {
+    let mut cells: Vec<Coord> = visible.find_all(|v| *v).collect();
+    cells.sort_by_key(|c| c.manhattan_dist_to(loc));
+
+    for c in cells {
+        if c == loc { continue }
+        let (sx, sy) = ((loc.0 - c.0).signum(), (loc.1 - c.1).signum());
+        let lit = |pt: Coord| pt == loc || (visible[pt] && transparent[pt]);
+        let horiz = xy(c.0 + sx, c.1);
+        let vert = xy(c.0, c.1 + sy);
+
+        let reachable = (sx != 0 && lit(horiz)) || (sy != 0 && lit(vert)) ||
+            (sx != 0 && sy != 0 && lit(xy(c.0 + sx, c.1 + sy)) && (transparent[horiz] || transparent[vert]));
+        if !reachable { visible[c] = false }
+    }
+}
+
+/// Like `shadowcast`, but uses the "symmetric shadowcasting" algorithm described here:
+/// https://www.albertford.com/shadowcasting/
+/// Visibility between floor cells is mutual: if A can see B, then B can see A, which isn't always
+/// true of `shadowcast`. This matters for things like stealth, where it's unfair for a monster to
+/// see the player without the player being able to see it.
+/// Like `shadowcast`, the grid is of which cells are transparent, and a radius of 0 is unlimited.
+/// ```
+/// # use heart437::*;
+/// let transparent = VecGrid::new(xy(10, 10), true);
+/// let visible = symmetric_shadowcast(transparent, xy(5, 5), 3);
+/// assert!(visible[xy(5, 3)]);
+/// assert!(!visible[xy(5, 1)]);
+/// ```
+pub fn symmetric_shadowcast<G: Grid<CellType=bool>>(grid: G, loc: Coord, radius: u32) -> VecGrid<bool> {
+    let size = grid.size();
+    let max_depth = if radius == 0 { size.0.max(size.1) } else { radius as i32 };
+    let in_range = |dx: i32, dy: i32| radius == 0 || dx * dx + dy * dy <= max_depth * max_depth;
+    let mut visible = VecGrid::new(size, false);
+    if !grid.contains(loc) { return visible }
+    visible[loc] = true;
+
+    // Each quadrant maps (depth, column) in its own frame into a coord on the grid:
+    let quadrants: [fn(Coord, i32, i32) -> Coord; 4] = [
+        |o, depth, col| xy(o.0 + col, o.1 - depth), // North
+        |o, depth, col| xy(o.0 + col, o.1 + depth), // South
+        |o, depth, col| xy(o.0 + depth, o.1 + col), // East
+        |o, depth, col| xy(o.0 - depth, o.1 + col), // West
+    ];
+
+    for transform in quadrants {
+        // Off-grid cells count as walls, so the scan always stops at the edges:
+        let is_wall = |depth, col| !*grid.get(transform(loc, depth, col)).unwrap_or(&false);
+        let mut rows = vec![Row { depth: 1, start: Slope(-1, 1), end: Slope(1, 1) }];
+
+        while let Some(mut row) = rows.pop() {
+            if row.depth > max_depth { continue }
+            let mut prev_wall = None;
+            for col in row.min_col()..=row.max_col() {
+                let wall = is_wall(row.depth, col);
+                if (wall || row.is_symmetric(col)) && in_range(col, row.depth) {
+                    if let Some(v) = visible.get_mut(transform(loc, row.depth, col)) { *v = true }
+                }
+                if prev_wall == Some(true) && !wall {
+                    row.start = Slope::of(row.depth, col);
+                }
+                if prev_wall == Some(false) && wall {
+                    rows.push(Row { depth: row.depth + 1, start: row.start, end: Slope::of(row.depth, col) });
+                }
+                prev_wall = Some(wall);
+            }
+            if prev_wall == Some(false) {
+                rows.push(Row { depth: row.depth + 1, ..row });
+            }
+        }
+    }
+
+    visible
+}
+
+/// Whether `from` can see `to` in a straight line: every cell between them (walking the grid one
+/// orthogonal step at a time) has to pass `transparent`. The endpoints themselves don't block, so
+/// an archer can shoot a monster standing in a doorway. Cells outside the grid block. For a
+/// single ranged attack this is a lot cheaper than a whole `shadowcast`.
+/// ```
+/// # use heart437::*;
+/// let map = VecGrid::from("@.#.\n....\n...M");
+/// assert!(line_of_sight(&map, xy(0, 0), xy(3, 2), |c| *c != '#'));
+/// assert!(!line_of_sight(&map, xy(0, 0), xy(3, 0), |c| *c != '#'));
+/// ```
+pub fn line_of_sight<G: Grid>(grid: &G, from: Coord, to: Coord, transparent: impl Fn(&G::CellType) -> bool) -> bool {
+    WalkGrid::new(from.into(), to.into())
+        .map(Coord::from)
+        .filter(|pt| *pt != from && *pt != to)
+        .all(|pt| grid.get(pt).is_some_and(&transparent))
+}
+
+/// A slope as an exact fraction (numerator, positive denominator), so the edges of the shadows
+/// don't depend on floating-point rounding
+#[derive(Copy, Clone)]
+struct Slope(i32, i32);
+
+impl Slope {
+    /// The slope of the left edge of a cell
+    fn of(depth: i32, col: i32) -> Slope {
+        Slope(2 * col - 1, 2 * depth)
+    }
+}
+
+/// A single row of a quadrant in `symmetric_shadowcast`, between two slopes
+#[derive(Copy, Clone)]
+struct Row {
+    depth: i32,
+    start: Slope,
+    end: Slope
+}
+
+impl Row {
+    /// depth * start, rounding ties up
+    fn min_col(&self) -> i32 {
+        let Slope(num, den) = self.start;
+        (2 * self.depth * num + den).div_euclid(2 * den)
+    }
+
+    /// depth * end, rounding ties down
+    fn max_col(&self) -> i32 {
+        let Slope(num, den) = self.end;
+        -(den - 2 * self.depth * num).div_euclid(2 * den)
+    }
+
+    /// Is the center of this column within the row's slopes?
+    fn is_symmetric(&self, col: i32) -> bool {
+        col * self.start.1 >= self.depth * self.start.0 &&
+            col * self.end.1 <= self.depth * self.end.0
+    }
+}
+
 trait Doryenable {
     fn mapdata(&self) -> MapData;
 }
@@ -66,4 +238,61 @@ mod test {
         let actual: String = visible.into();
         assert_eq!(actual.as_str(), expected)
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_symmetric_fov() {
+        let map = VecGrid::from("......\n\
+                                 ..+...\n\
+                                 ......\n\
+                                 ....+.\n\
+                                 ......");
+        let transparent = map.map_grid(|_, ch| *ch == '.', false);
+        let (a, b) = (xy(0, 0), xy(3, 1));
+
+        // The recursive shadowcast lets a see b, but not b see a:
+        assert!(shadowcast(transparent.clone(), a, 20)[b]);
+        assert!(!shadowcast(transparent.clone(), b, 20)[a]);
+
+        // The symmetric one is always mutual, between any two floor cells:
+        for a in transparent.find_all(|t| *t) {
+            let from_a = symmetric_shadowcast(transparent.clone(), a, 20);
+            for b in transparent.find_all(|t| *t) {
+                let from_b = symmetric_shadowcast(transparent.clone(), b, 20);
+                assert_eq!(from_a[b], from_b[a], "{} and {} disagree", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn test_diagonal_blocking() {
+        // The viewer at (2, 0) is looking between two diagonally-touching walls at (3, 1):
+        let map = VecGrid::from("...+.\n\
+                                 ..+..\n\
+                                 .....");
+        let transparent = map.map_grid(|_, ch| *ch == '.', false);
+        let (viewer, gap) = (xy(2, 0), xy(3, 1));
+
+        let peeking = shadowcast_with_opts(transparent.clone(), viewer, 20, FovOpts::new());
+        assert!(peeking[gap]);
+        let blocked = shadowcast_with_opts(transparent.clone(), viewer, 20, FovOpts::new().with_diagonal_blocking(true));
+        assert!(!blocked[gap]);
+
+        // Nothing else in front of the walls changes:
+        assert!(blocked[xy(1, 1)] && blocked[xy(3, 0)] && blocked[xy(2, 1)]);
+    }
+
+    #[test]
+    fn test_line_of_sight() {
+        let map = VecGrid::from("....\n.#..\n....\n#...");
+        let clear = |c: &char| *c == '.';
+        assert!(line_of_sight(&map, xy(0, 0), xy(3, 0), clear));
+        assert!(!line_of_sight(&map, xy(0, 1), xy(3, 1), clear));
+        assert!(!line_of_sight(&map, xy(3, 1), xy(0, 1), clear));
+        // The endpoints don't block:
+        assert!(line_of_sight(&map, xy(1, 1), xy(3, 1), clear));
+        assert!(line_of_sight(&map, xy(3, 3), xy(0, 3), clear));
+        assert!(line_of_sight(&map, xy(2, 2), xy(2, 2), clear));
+        // Orthogonal neighbors can always see each other:
+        assert!(line_of_sight(&map, xy(1, 1), xy(1, 0), |_| false));
+    }
+}
@@ -1,4 +1,10 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::Hash;
+use std::ops::{Index, IndexMut};
 use crate::coords::{Coord, xy};
+use crate::VecGrid;
+#[cfg(feature = "rand")]
+use rand::Rng;
 
 /// A trait for operations on a 2d grid of objects
 pub trait Grid {
@@ -16,6 +22,36 @@ pub trait Grid {
     /// the size of the grid, and `None` for any point outside the grid.
     fn get(&self, index: Coord) -> Option<&Self::CellType>;
 
+    /// Get a cell in the grid, clamping the coordinate to the nearest edge if it's outside the
+    /// grid. This never returns the default: sampling past an edge just extends that edge. It's
+    /// only `None` if the grid is empty (zero wide or tall), so there's no edge to clamp to.
+    /// ```
+    /// # use heart437::*;
+    /// let grid = VecGrid::from("AB\nCD");
+    /// assert_eq!(grid.get_clamped(xy(5, -1)), Some(&'B'));
+    /// ```
+    fn get_clamped(&self, index: Coord) -> Option<&Self::CellType> {
+        let Coord(width, height) = self.size();
+        if width <= 0 || height <= 0 { return None }
+        self.get(xy(index.0.clamp(0, width - 1), index.1.clamp(0, height - 1)))
+    }
+
+    /// Get a cell in the grid, wrapping the coordinate around to the other side if it's outside
+    /// the grid, as though the grid were a torus. This never returns the default, which makes it
+    /// good for wrap-around worlds and for cellular automata without edges. Like `get_clamped`,
+    /// it's only `None` if the grid is empty.
+    /// ```
+    /// # use heart437::*;
+    /// let grid = VecGrid::from("AB\nCD");
+    /// assert_eq!(grid.get_wrapping(xy(-1, 0)), Some(&'B'));
+    /// assert_eq!(grid.get_wrapping(xy(2, 3)), Some(&'C'));
+    /// ```
+    fn get_wrapping(&self, index: Coord) -> Option<&Self::CellType> {
+        let Coord(width, height) = self.size();
+        if width <= 0 || height <= 0 { return None }
+        self.get(xy(index.0.rem_euclid(width), index.1.rem_euclid(height)))
+    }
+
     /// Is a given point inside the grid?
     fn contains(&self, point: Coord) -> bool {
         let dims = self.size();
@@ -87,6 +123,36 @@ pub trait Grid {
         c.into_iter().filter(|pt| self.contains(*pt))
     }
 
+    /// The coordinates of all eight of our neighbors, orthogonal and diagonal, but only the ones
+    /// actually in the grid. They go clockwise, starting from north.
+    /// ```
+    /// # use heart437::*;
+    /// let grid = VecGrid::new(xy(10, 10), 0);
+    /// assert_eq!(grid.neighbor_coords8(xy(5, 5)).count(), 8);
+    /// assert_eq!(grid.neighbor_coords8(xy(0, 0)).collect::<Vec<_>>(), vec![xy(1, 0), xy(1, 1), xy(0, 1)]);
+    /// ```
+    fn neighbor_coords8(&self, point: Coord) -> impl Iterator<Item=Coord> {
+        let c = vec![point.north(), point.northeast(), point.east(), point.southeast(),
+                     point.south(), point.southwest(), point.west(), point.northwest()];
+        c.into_iter().filter(|pt| self.contains(*pt))
+    }
+
+    /// The coordinates of the cells around the edge of the grid, in reading order
+    /// ```
+    /// # use heart437::*;
+    /// let mut map = VecGrid::new(xy(10, 10), false);
+    /// let edges: Vec<Coord> = map.border_coords().collect();
+    /// map.set_many(edges.into_iter().map(|pt| (pt, true)));
+    /// ```
+    fn border_coords(&self) -> impl Iterator<Item=Coord> {
+        let Coord(width, height) = self.size();
+        (0..height).flat_map(move |y| {
+            // Edge rows are every cell; the rest are just the first and last:
+            let step = if y == 0 || y == height - 1 { 1 } else { (width - 1).max(1) };
+            (0..width).step_by(step as usize).map(move |x| xy(x, y))
+        })
+    }
+
     /// Convenience method for `for_neighbors` just comparing with ==
     fn neighbors_equal(&self, point: Coord, val: Self::CellType) -> (bool, bool, bool, bool)
         where Self::CellType: PartialEq {
@@ -108,10 +174,137 @@ pub trait Grid {
         None
     }
 
+    /// Returns the first (in reading order) non-`None` result of running a function on each cell,
+    /// like `Iterator::find_map`:
+    /// ```
+    /// # use heart437::*;
+    /// let grid = VecGrid::from("..\n.7");
+    /// let found = grid.find_map(|c, ch| ch.to_digit(10).map(|d| (c, d)));
+    /// assert_eq!(found, Some((xy(1, 1), 7)));
+    /// ```
+    fn find_map<A, F: Fn(Coord, &Self::CellType) -> Option<A>>(&self, func: F) -> Option<A> {
+        for c in self.size() {
+            if let Some(a) = func(c, self.get(c).unwrap()) { return Some(a) }
+        }
+        None
+    }
+
     /// Return an iterator of all the coords that match a certain predicate
     fn find_all<'a, F: Fn(&Self::CellType) -> bool + 'a>(&'a self, test: F) -> impl Iterator<Item=Coord> {
         self.size().into_iter().filter(move |c| test(self.get(*c).unwrap()))
     }
+
+    /// The smallest rectangle holding every cell that matches a predicate, as its top-left and
+    /// bottom-right corners (both inclusive), or `None` if nothing matches. Handy for cropping a
+    /// generated map down to its contents.
+    /// ```
+    /// # use heart437::*;
+    /// let map = VecGrid::from(".....\n..#..\n.#...\n.....");
+    /// assert_eq!(map.bounding_box(|c| *c == '#'), Some((xy(1, 1), xy(2, 2))));
+    /// assert_eq!(map.bounding_box(|c| *c == '@'), None);
+    /// ```
+    fn bounding_box<F: Fn(&Self::CellType) -> bool>(&self, test: F) -> Option<(Coord, Coord)> {
+        self.find_all(test).fold(None, |bounds, pt| match bounds {
+            None => Some((pt, pt)),
+            Some((min, max)) => Some((xy(min.0.min(pt.0), min.1.min(pt.1)), xy(max.0.max(pt.0), max.1.max(pt.1))))
+        })
+    }
+
+    /// All the cells connected to `start` through orthogonal neighbors that pass a test, such
+    /// as every floor cell reachable from a door, in breadth-first order starting with `start`.
+    /// Empty if `start` itself doesn't pass, or isn't in the grid.
+    /// ```
+    /// # use heart437::*;
+    /// let map = VecGrid::from("..#.\n..#.\n###.");
+    /// let room = map.flood_region(xy(0, 0), |c| *c == '.');
+    /// assert_eq!(room, vec![xy(0, 0), xy(1, 0), xy(0, 1), xy(1, 1)]);
+    /// ```
+    fn flood_region<F: Fn(&Self::CellType) -> bool>(&self, start: Coord, traversable: F) -> Vec<Coord> {
+        if !self.get(start).is_some_and(&traversable) { return vec![] }
+        let mut region = vec![];
+        let mut seen = HashSet::from([start]);
+        let mut open = VecDeque::from([start]);
+
+        while let Some(curr) = open.pop_front() {
+            region.push(curr);
+            for n in self.neighbor_coords(curr) {
+                if traversable(self.get(n).unwrap()) && seen.insert(n) {
+                    open.push_back(n)
+                }
+            }
+        }
+        region
+    }
+
+    /// Count how many times each distinct value appears in the grid
+    /// ```
+    /// # use heart437::*;
+    /// let map = VecGrid::from("#..\n##.").map_grid(|_, ch| *ch == '#', false);
+    /// let counts = map.histogram();
+    /// assert_eq!(counts[&true], 3);
+    /// assert_eq!(counts[&false], 3);
+    /// ```
+    fn histogram(&self) -> HashMap<Self::CellType, usize> where Self::CellType: Eq + Hash + Clone {
+        let mut counts = HashMap::new();
+        for cell in self.iter() {
+            *counts.entry(cell.clone()).or_insert(0) += 1
+        }
+        counts
+    }
+
+    /// Shrink a grid of bools by some factor, such as for a minimap. Each `factor` x `factor`
+    /// block of cells becomes one cell, which is true if at least half of the block is true (so
+    /// a thin wall doesn't vanish). Blocks hanging off the right or bottom edge only count the
+    /// cells actually in the grid.
+    /// ```
+    /// # use heart437::*;
+    /// let map = VecGrid::new(xy(80, 60), false);
+    /// let minimap = map.downsample(4);
+    /// assert_eq!(minimap.size(), xy(20, 15));
+    /// ```
+    fn downsample(&self, factor: i32) -> VecGrid<bool> where Self: Grid<CellType=bool> {
+        let factor = factor.max(1);
+        let Coord(width, height) = self.size();
+        let size = xy((width + factor - 1) / factor, (height + factor - 1) / factor);
+        let mut small = VecGrid::new(size, false);
+
+        for pt in size {
+            let block = Coord::iter_from(pt * factor, pt * factor + xy(factor, factor));
+            let (total, set) = block.filter_map(|c| self.get(c))
+                .fold((0, 0), |(total, set), v| (total + 1, set + *v as i32));
+            small[pt] = set * 2 >= total;
+        }
+        small
+    }
+
+    /// Pick a random coord, where the chance of each cell being chosen is proportional to the
+    /// weight the given function assigns it. Cells weighted zero (or less) are never chosen; if
+    /// every cell is, this returns `None`.
+    /// ```
+    /// # use heart437::*;
+    /// # use rand::SeedableRng;
+    /// let danger = VecGrid::from_vec(vec![0.0, 1.0, 3.0], 3, 0.0);
+    /// let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+    /// let spawn = danger.weighted_choice(&mut rng, |d| *d);
+    /// assert_ne!(spawn, Some(xy(0, 0)));
+    /// ```
+    #[cfg(feature = "rand")]
+    fn weighted_choice<R: Rng, F: Fn(&Self::CellType) -> f32>(&self, rng: &mut R, weight: F) -> Option<Coord> {
+        let total: f32 = self.iter().map(|c| weight(c).max(0.0)).sum();
+        if total <= 0.0 { return None }
+
+        let mut target = rng.gen::<f32>() * total;
+        let mut last = None;
+        for c in self.size() {
+            let w = weight(self.get(c).unwrap());
+            if w > 0.0 {
+                if target < w { return Some(c) }
+                target -= w;
+                last = Some(c);
+            }
+        }
+        last // Only reachable through float rounding, in which case it's the last one
+    }
 }
 
 /// A trait that can be applied to any `Grid` to represent mutating cells in the grid.
@@ -119,6 +312,68 @@ pub trait GridMut: Grid {
     /// This behaves just like `get`: it must return `Some` for any coord in the bounds of the
     /// grid and `None` outside.
     fn get_mut(&mut self, index: Coord) -> Option<&mut Self::CellType>;
+
+    /// Replace every cell with the result of a function of its orthogonal neighbors `(n, s, e, w)`
+    /// and itself, for cellular-automata-style rules. The neighbors are always read from a snapshot
+    /// of the grid before any cells change, and any outside the grid are the default.
+    /// ```
+    /// # use heart437::*;
+    /// let mut grid = VecGrid::from(".#.\n...");
+    /// // Spread the '#' into every neighboring cell:
+    /// grid.update_with_neighbors(|(n, s, e, w), me| {
+    ///     if [n, s, e, w, me].contains(&&'#') { '#' } else { '.' }
+    /// });
+    /// ```
+    fn update_with_neighbors<F>(&mut self, func: F)
+        where Self::CellType: Clone,
+              F: Fn((&Self::CellType, &Self::CellType, &Self::CellType, &Self::CellType), &Self::CellType) -> Self::CellType {
+        let size = self.size();
+        let def = self.default();
+        let old: Vec<Self::CellType> = self.iter().cloned().collect();
+        let at = |pt: Coord| if pt.within(size) { &old[(pt.0 + pt.1 * size.0) as usize] } else { &def };
+
+        for pt in size {
+            let new = func((at(pt.north()), at(pt.south()), at(pt.east()), at(pt.west())), at(pt));
+            *self.get_mut(pt).unwrap() = new;
+        }
+    }
+
+    /// Set a batch of cells at once. Any updates for coords outside the grid are skipped.
+    /// ```
+    /// # use heart437::*;
+    /// let mut grid = VecGrid::new(xy(3, 3), '.');
+    /// grid.set_many(vec![(xy(0, 0), '#'), (xy(2, 1), '@'), (xy(5, 5), '!')]);
+    /// ```
+    fn set_many<I: IntoIterator<Item=(Coord, Self::CellType)>>(&mut self, items: I) {
+        for (pt, val) in items {
+            if let Some(cell) = self.get_mut(pt) { *cell = val }
+        }
+    }
+}
+
+/// Split the cells that pass `member` into connected components (through orthogonal neighbors,
+/// like `flood_region`), such as the separate caves of a map. Returns a grid of component IDs,
+/// numbered from 1 in reading order of each component's first cell, with 0 for non-members,
+/// and how many components there are.
+/// ```
+/// # use heart437::*;
+/// let map = VecGrid::from("..#.\n###.\n.#..");
+/// let (ids, count) = label_components(&map, |c| *c == '.');
+/// assert_eq!(count, 3);
+/// assert_eq!((ids[xy(1, 0)], ids[xy(3, 2)], ids[xy(0, 2)], ids[xy(2, 0)]), (1, 2, 3, 0));
+/// ```
+pub fn label_components<G: Grid>(grid: &G, member: impl Fn(&G::CellType) -> bool) -> (VecGrid<i32>, usize) {
+    let mut ids = VecGrid::new(grid.size(), 0);
+    let mut count = 0;
+
+    for pt in grid.size() {
+        if ids[pt] != 0 || !member(grid.get(pt).unwrap()) { continue }
+        count += 1;
+        for c in grid.flood_region(pt, &member) {
+            ids[c] = count as i32
+        }
+    }
+    (ids, count)
 }
 
 /// Trait impld on `(bool, bool, bool, bool)` to make it easy to count
@@ -140,6 +395,101 @@ impl CountableNeighbors for (bool, bool, bool, bool) {
     }
 }
 
+/// A window into a rectangle of some larger grid, without copying it: cell (0, 0) of the
+/// subgrid is cell `pos` of the parent. Every `Grid` method works within the window, so a room
+/// can be processed on its own, or just a viewport fed to FOV. Use `SubGridMut` to edit the
+/// parent through the window.
+/// ```
+/// # use heart437::*;
+/// let map = VecGrid::from("#####\n#...#\n#.#.#\n#####");
+/// let room = SubGrid::new(&map, xy(1, 1), xy(3, 2));
+/// assert_eq!(room.get(xy(1, 1)), Some(&'#'));
+/// assert_eq!(room.find_all(|c| *c == '.').count(), 5);
+/// ```
+pub struct SubGrid<'a, G: Grid> {
+    parent: &'a G,
+    pos: Coord,
+    size: Coord
+}
+
+impl<'a, G: Grid> SubGrid<'a, G> {
+    /// A window of `size` cells starting at `pos` in the parent. Panics if the window isn't
+    /// entirely inside the parent!
+    pub fn new(parent: &'a G, pos: Coord, size: Coord) -> Self {
+        assert_window(parent, pos, size);
+        Self { parent, pos, size }
+    }
+}
+
+impl<G: Grid> Grid for SubGrid<'_, G> {
+    type CellType = G::CellType;
+    fn size(&self) -> Coord { self.size }
+    fn default(&self) -> Self::CellType { self.parent.default() }
+    fn get(&self, index: Coord) -> Option<&Self::CellType> {
+        if self.contains(index) { self.parent.get(self.pos + index) } else { None }
+    }
+}
+
+impl<G: Grid> Index<Coord> for SubGrid<'_, G> {
+    type Output = G::CellType;
+    fn index(&self, index: Coord) -> &Self::Output { self.get(index).unwrap() }
+}
+
+/// Like `SubGrid`, but borrows the parent mutably so cells can be changed through the window.
+/// ```
+/// # use heart437::*;
+/// let mut map = VecGrid::new(xy(10, 10), '#');
+/// let mut room = SubGridMut::new(&mut map, xy(2, 2), xy(4, 3));
+/// for pt in room.size() { room[pt] = '.' }
+/// assert_eq!(map[xy(5, 4)], '.');
+/// assert_eq!(map[xy(6, 4)], '#');
+/// ```
+pub struct SubGridMut<'a, G: GridMut> {
+    parent: &'a mut G,
+    pos: Coord,
+    size: Coord
+}
+
+impl<'a, G: GridMut> SubGridMut<'a, G> {
+    /// A window of `size` cells starting at `pos` in the parent. Panics if the window isn't
+    /// entirely inside the parent!
+    pub fn new(parent: &'a mut G, pos: Coord, size: Coord) -> Self {
+        assert_window(parent, pos, size);
+        Self { parent, pos, size }
+    }
+}
+
+impl<G: GridMut> Grid for SubGridMut<'_, G> {
+    type CellType = G::CellType;
+    fn size(&self) -> Coord { self.size }
+    fn default(&self) -> Self::CellType { self.parent.default() }
+    fn get(&self, index: Coord) -> Option<&Self::CellType> {
+        if self.contains(index) { self.parent.get(self.pos + index) } else { None }
+    }
+}
+
+impl<G: GridMut> GridMut for SubGridMut<'_, G> {
+    fn get_mut(&mut self, index: Coord) -> Option<&mut Self::CellType> {
+        if self.contains(index) { self.parent.get_mut(self.pos + index) } else { None }
+    }
+}
+
+impl<G: GridMut> Index<Coord> for SubGridMut<'_, G> {
+    type Output = G::CellType;
+    fn index(&self, index: Coord) -> &Self::Output { self.get(index).unwrap() }
+}
+
+impl<G: GridMut> IndexMut<Coord> for SubGridMut<'_, G> {
+    fn index_mut(&mut self, index: Coord) -> &mut Self::Output { self.get_mut(index).unwrap() }
+}
+
+fn assert_window<G: Grid>(parent: &G, pos: Coord, size: Coord) {
+    assert!(size.0 >= 0 && size.1 >= 0, "Subgrid size can't be negative!");
+    let inside = size.0 == 0 || size.1 == 0 ||
+        (parent.contains(pos) && parent.contains(pos + size + xy(-1, -1)));
+    assert!(inside, "Subgrid must be inside its parent grid!");
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -159,6 +509,16 @@ mod tests {
         }
     }
 
+    impl GridMut for TestGrid {
+        fn get_mut(&mut self, index: Coord) -> Option<&mut char> {
+            if self.contains(index) {
+                Some(&mut self.0[index.0 as usize + (index.1 * self.1) as usize])
+            } else {
+                None
+            }
+        }
+    }
+
     impl From<&str> for TestGrid {
         fn from(value: &str) -> Self {
             let lines: Vec<Vec<_>> = value.lines().map(|line| line.chars().collect()).collect();
@@ -190,4 +550,190 @@ mod tests {
         // One near the edge:
         assert_eq!(grid.neighbors_equal(xy(1, 0), 'B'), (false, true, false, false))
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_find_map() {
+        let grid = TestGrid::from("ab\nCD");
+        let found = grid.find_map(|c, ch| if ch.is_uppercase() { Some((c, *ch)) } else { None });
+        assert_eq!(found, Some((xy(0, 1), 'C')));
+        assert_eq!(grid.find_map(|_, ch| if *ch == 'z' { Some(()) } else { None }), None);
+    }
+
+    #[test]
+    fn test_get_clamped() {
+        let grid = TestGrid::from("AB\nCD");
+        assert_eq!(grid.get_clamped(xy(-5, -5)), Some(&'A'));
+        assert_eq!(grid.get_clamped(xy(1, 0)), Some(&'B'));
+        assert_eq!(grid.get_clamped(xy(-1, 7)), Some(&'C'));
+        assert_eq!(grid.get_clamped(xy(9, 9)), Some(&'D'));
+
+        // Nothing to clamp to in an empty grid:
+        assert_eq!(VecGrid::new(xy(3, 0), 'A').get_clamped(xy(0, 0)), None);
+        assert_eq!(SubGrid::new(&grid, xy(1, 0), xy(0, 2)).get_clamped(xy(0, 0)), None);
+    }
+
+    #[test]
+    fn test_histogram() {
+        let grid = TestGrid::from("#.#\n@..");
+        let counts = grid.histogram();
+        assert_eq!(counts.len(), 3);
+        assert_eq!(counts[&'#'], 2);
+        assert_eq!(counts[&'.'], 3);
+        assert_eq!(counts[&'@'], 1);
+        assert_eq!(counts.values().sum::<usize>(), 6);
+    }
+
+    #[test]
+    fn test_downsample() {
+        let full = VecGrid::new(xy(4, 4), true).downsample(2);
+        assert_eq!(full.size(), xy(2, 2));
+        assert!(full.iter().all(|v| *v));
+
+        // Top-left block is 3/4 set, top-right 1/4, bottom-left 2/4, bottom-right empty:
+        let mixed = VecGrid::from("##.#\n\
+                                   #...\n\
+                                   #...\n\
+                                   .#..").map_grid(|_, ch| *ch == '#', false);
+        let small = mixed.downsample(2);
+        assert_eq!(small.iter().cloned().collect::<Vec<_>>(), vec![true, false, true, false]);
+    }
+
+    #[test]
+    fn test_border_coords() {
+        let grid = TestGrid::from("...\n...\n...");
+        let border: Vec<Coord> = grid.border_coords().collect();
+        assert_eq!(border, vec![xy(0, 0), xy(1, 0), xy(2, 0),
+                                xy(0, 1), xy(2, 1),
+                                xy(0, 2), xy(1, 2), xy(2, 2)]);
+
+        let column = TestGrid::from(".\n.\n.");
+        assert_eq!(column.border_coords().count(), 3);
+    }
+
+    #[test]
+    fn test_set_many() {
+        let mut grid = TestGrid::from("...\n...");
+        grid.set_many(vec![(xy(0, 0), 'A'), (xy(2, 1), 'B'), (xy(3, 0), 'C'), (xy(-1, 1), 'D')]);
+        // The out-of-bounds updates were skipped:
+        assert_eq!(grid.0.iter().collect::<String>(), "A....B");
+    }
+
+    #[test]
+    fn test_update_with_neighbors() {
+        // A Life-like rule on just the orthogonal neighbors: a cell with two live neighbors is
+        // born or survives, and a live one with three survives.
+        let mut grid = TestGrid::from("##.\n#..\n...");
+        grid.update_with_neighbors(|(n, s, e, w), me| {
+            let live = [n, s, e, w].iter().filter(|c| ***c == '#').count();
+            if live == 2 || (*me == '#' && live == 3) { '#' } else { '.' }
+        });
+        assert_eq!(grid.0.iter().collect::<String>(), "#...#....");
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn test_weighted_choice() {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(3);
+        let grid = TestGrid::from("..\n.X");
+        let weight = |c: &char| if *c == 'X' { 1000000.0 } else { 0.001 };
+        for _ in 0..10 {
+            assert_eq!(grid.weighted_choice(&mut rng, weight), Some(xy(1, 1)));
+        }
+
+        assert_eq!(grid.weighted_choice(&mut rng, |_| 0.0), None);
+    }
+
+    #[test]
+    fn test_flood_region() {
+        let grid = TestGrid::from("....\n.++.\n.+..");
+        let cs = grid.flood_region(xy(1, 1), |ch| *ch == '+');
+        assert!(cs.contains(&xy(1, 1)));
+        assert!(cs.contains(&xy(2, 1)));
+        assert!(cs.contains(&xy(1, 2)));
+        assert_eq!(cs.len(), 3);
+
+        assert_eq!(grid.flood_region(xy(0, 0), |ch| *ch == '+'), vec![]);
+        assert_eq!(grid.flood_region(xy(9, 9), |ch| *ch == '+'), vec![]);
+        let dots = grid.flood_region(xy(0, 2), |ch| *ch == '.');
+        assert_eq!(dots.len(), 9);
+        assert_eq!(&dots[0..3], &[xy(0, 2), xy(0, 1), xy(0, 0)]);
+    }
+
+    #[test]
+    fn test_bounding_box() {
+        let grid = TestGrid::from("....\n..+.\n....\n+...");
+        assert_eq!(grid.bounding_box(|ch| *ch == '+'), Some((xy(0, 1), xy(2, 3))));
+        assert_eq!(grid.bounding_box(|ch| *ch == '.'), Some((xy(0, 0), xy(3, 3))));
+        assert_eq!(grid.bounding_box(|ch| *ch == '#'), None);
+
+        let single = TestGrid::from("...\n.+.");
+        assert_eq!(single.bounding_box(|ch| *ch == '+'), Some((xy(1, 1), xy(1, 1))));
+    }
+
+    #[test]
+    fn test_label_components() {
+        let grid = TestGrid::from("+.+\n+.+\n.++");
+        let (ids, count) = label_components(&grid, |ch| *ch == '+');
+        assert_eq!(count, 2);
+        let labels: Vec<i32> = grid.size().into_iter().map(|pt| ids[pt]).collect();
+        assert_eq!(labels, vec![1, 0, 2, 1, 0, 2, 0, 2, 2]);
+
+        let (ids, count) = label_components(&grid, |ch| *ch == '#');
+        assert_eq!(count, 0);
+        assert!(ids.iter().all(|id| *id == 0));
+    }
+
+    #[test]
+    fn test_neighbor_coords8() {
+        let grid = TestGrid::from("ABC\nDEF\nGHI");
+        let around = |pt| grid.neighbor_coords8(pt).map(|c| *grid.get(c).unwrap()).collect::<String>();
+        assert_eq!(around(xy(1, 1)), "BCFIHGDA");
+        assert_eq!(around(xy(2, 0)), "FEB");
+        assert_eq!(around(xy(0, 2)), "DEH");
+    }
+
+    #[test]
+    fn test_get_wrapping() {
+        let grid = TestGrid::from("ABC\nDEF");
+        assert_eq!(grid.get_wrapping(xy(1, 1)), Some(&'E'));
+        assert_eq!(grid.get_wrapping(xy(3, 0)), Some(&'A'));
+        assert_eq!(grid.get_wrapping(xy(-1, -1)), Some(&'F'));
+        assert_eq!(grid.get_wrapping(xy(-4, 5)), Some(&'F'));
+        assert_eq!(grid.get_wrapping(xy(7, -2)), Some(&'B'));
+
+        // Nothing to wrap around to in an empty grid:
+        assert_eq!(VecGrid::new(xy(3, 0), 'A').get_wrapping(xy(1, 1)), None);
+        assert_eq!(SubGrid::new(&grid, xy(1, 0), xy(0, 2)).get_wrapping(xy(0, 0)), None);
+    }
+
+    #[test]
+    fn test_subgrid() {
+        let grid = TestGrid::from("ABCD\nEFGH\nIJKL");
+        let sub = SubGrid::new(&grid, xy(1, 1), xy(3, 2));
+        assert_eq!(sub.size(), xy(3, 2));
+        assert_eq!(sub.iter().collect::<String>(), "FGHJKL");
+        assert_eq!(sub[xy(0, 0)], 'F');
+        assert_eq!(sub.get(xy(3, 0)), None);
+        assert_eq!(sub.get(xy(-1, 0)), None); // In the parent, but not the window
+        assert_eq!(sub.neighbors_equal(xy(0, 0), 'B'), (false, false, false, false));
+        assert_eq!(sub.neighbor_coords(xy(0, 0)).count(), 2);
+    }
+
+    #[test]
+    fn test_subgrid_mut() {
+        let mut grid = TestGrid::from("ABCD\nEFGH\nIJKL");
+        let mut sub = SubGridMut::new(&mut grid, xy(2, 0), xy(2, 3));
+        sub[xy(1, 2)] = 'z';
+        sub.set_many([(xy(0, 0), 'y'), (xy(2, 0), '!')]);
+        assert_eq!(sub.get_mut(xy(0, 3)), None);
+        assert_eq!(grid.iter().collect::<String>(), "AByDEFGHIJKz");
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_subgrid_outside() {
+        let grid = TestGrid::from("ABCD\nEFGH\nIJKL");
+        SubGrid::new(&grid, xy(2, 2), xy(3, 1));
+    }
+}
@@ -1,7 +1,9 @@
+use std::fmt::{Display, Formatter};
 use std::ops::{Index, IndexMut};
 use crate::color::{Color};
-use crate::font::{Font, Glyph};
-use crate::{Cell, Char, Coord, pxy, Sprite, VecGrid, xy};
+use crate::font::{Font, Glyph, ScaledGlyphs};
+use crate::{Bg, Cell, Char, Coord, Fg, OptionCell, pxy, Sprite, Style, VecGrid, xy};
+use crate::drawing::wall_piece;
 use crate::coords::PixelCoord;
 use crate::grid::{Grid, GridMut};
 
@@ -20,8 +22,19 @@ pub struct Layer<'a> {
     /// Where to place the layer in the target texture
     pub origin: PixelCoord,
 
+    /// How opaque the whole layer is when drawn, from 0 (invisible) to 1 (the default), for
+    /// fading it in and out. Out-of-range values are clamped. This multiplies the alpha of every
+    /// color the layer draws, including sprites and proportional text. Changing it doesn't mark
+    /// any cells dirty, so when fading with `draw_dirty`, call `mark_all_dirty` after each change.
+    pub opacity: f32,
+
     width: i32,
-    data: Vec<Cell>
+    data: Vec<Cell>,
+    cursor: Option<Coord>,
+    /// Which cells have changed since `draw_dirty` last drew them
+    dirty: Vec<bool>,
+    /// Glyphs expanded to the scales they've been drawn at
+    scaled: ScaledGlyphs
 }
 
 impl<'a> Layer<'a> {
@@ -38,8 +51,224 @@ impl<'a> Layer<'a> {
             font,
             scale,
             origin,
+            opacity: 1.0,
             data,
-            width: size.0
+            width: size.0,
+            cursor: None,
+            dirty: vec![true; len],
+            scaled: ScaledGlyphs::default()
+        }
+    }
+
+    /// Apply an `OptionCell` to the cell at a given coordinate, changing only the fields it has.
+    /// This is what `Canvas::set` does for a layer; with `Canvas` in scope, that four-argument
+    /// version has to be called as `Canvas::set(&mut layer, ...)`. Panics if `at` is outside the
+    /// layer!
+    /// ```
+    /// # use heart437::*;
+    /// # let font = Font::default();
+    /// let mut layer = Layer::new(&font, xy(10, 10), pxy(1, 1), pxy(0, 0));
+    /// let brush = OptionCell::new().with_ch(b'~').with_fg(BLUE);
+    /// for x in 0..10 { layer.set(xy(x, 9), brush) }
+    /// ```
+    pub fn set(&mut self, at: Coord, cell: OptionCell) {
+        self[at] |= cell
+    }
+
+    /// Change the layer's size in cells, for when the window resizes. Cells inside both the old
+    /// and new sizes keep their contents, new cells are `Cell::default()`, and the cursor is
+    /// removed if it's no longer on the layer. Panics if the new size isn't at least 1x1!
+    /// ```
+    /// # use heart437::*;
+    /// # let font = Font::default();
+    /// let mut layer = Layer::new(&font, xy(80, 25), pxy(1, 1), pxy(0, 0));
+    /// layer[xy(3, 3)] |= Char(b'@');
+    /// layer.resize(xy(100, 40));
+    /// assert_eq!(layer[xy(3, 3)].ch, b'@');
+    /// ```
+    pub fn resize(&mut self, new_size: Coord) {
+        assert!(new_size.0 > 0 && new_size.1 > 0, "Layers must be at least 1x1!");
+        let mut data = vec![Cell::default(); (new_size.0 * new_size.1) as usize];
+        for pt in new_size {
+            if let Some(cell) = self.get(pt) {
+                data[(pt.0 + new_size.0 * pt.1) as usize] = *cell
+            }
+        }
+        self.dirty = vec![true; data.len()];
+        self.data = data;
+        self.width = new_size.0;
+        if self.cursor.is_some_and(|c| !self.contains(c)) {
+            self.cursor = None
+        }
+    }
+
+    /// Copy a rectangle of cells, given as `(position, size)`, from another layer into this one
+    /// with its top-left corner at `dest`. Unlike `compose` this copies cells as they are, which
+    /// makes it the way to show a viewport into a bigger offscreen map. Parts of the rectangle
+    /// outside the source layer, or that would land outside this one, are skipped.
+    /// ```
+    /// # use heart437::*;
+    /// # let font = Font::default();
+    /// let world = Layer::new(&font, xy(200, 200), pxy(1, 1), pxy(0, 0));
+    /// let mut screen = Layer::new(&font, xy(80, 25), pxy(1, 1), pxy(0, 0));
+    /// let camera = xy(57, 102);
+    /// screen.blit_region(&world, (camera, xy(80, 25)), xy(0, 0));
+    /// ```
+    pub fn blit_region(&mut self, src: &Layer, src_rect: (Coord, Coord), dest: Coord) {
+        let (pos, size) = src_rect;
+        for pt in size {
+            if let Some(cell) = src.get(pos + pt) {
+                if let Some(target) = self.get_mut(dest + pt) {
+                    *target = *cell
+                }
+            }
+        }
+    }
+
+    /// A copy of this layer with its grid rotated clockwise by some number of quarter turns (so 1
+    /// is 90 degrees, and 4 is back to normal). The glyphs themselves aren't rotated, only moved.
+    /// A quarter turn either way swaps the width and height. The copy has the same font, scale,
+    /// origin, and opacity, and the cursor moves with its cell.
+    /// ```
+    /// # use heart437::*;
+    /// # let font = Font::default();
+    /// let minimap = Layer::new(&font, xy(30, 20), pxy(1, 1), pxy(0, 0));
+    /// let facing_east = minimap.rotated(1);
+    /// assert_eq!(Grid::size(&facing_east), xy(20, 30));
+    /// ```
+    pub fn rotated(&self, quarter_turns: u8) -> Layer<'a> {
+        let Coord(w, h) = self.size();
+        let turns = quarter_turns % 4;
+        let rotate = |Coord(x, y): Coord| match turns {
+            1 => xy(h - 1 - y, x),
+            2 => xy(w - 1 - x, h - 1 - y),
+            3 => xy(y, w - 1 - x),
+            _ => xy(x, y)
+        };
+
+        let size = if turns % 2 == 1 { xy(h, w) } else { xy(w, h) };
+        let mut layer = Layer::new(self.font, size, self.scale, self.origin);
+        layer.opacity = self.opacity;
+        layer.cursor = self.cursor.map(rotate);
+        for pt in self.size() {
+            layer[rotate(pt)] = self[pt]
+        }
+        layer
+    }
+
+    /// Mirror the layer left-to-right in place. Only the cells are rearranged; the glyphs
+    /// themselves aren't mirrored. The cursor moves with its cell.
+    /// ```
+    /// # use heart437::*;
+    /// # let font = Font::default();
+    /// let mut room = Layer::new(&font, xy(10, 5), pxy(1, 1), pxy(0, 0));
+    /// room[xy(0, 2)] |= Char(b'+');
+    /// room.flip_h();
+    /// assert_eq!(room[xy(9, 2)].ch, b'+');
+    /// ```
+    pub fn flip_h(&mut self) {
+        let w = self.width as usize;
+        for row in self.data.chunks_mut(w) { row.reverse() }
+        self.cursor = self.cursor.map(|Coord(x, y)| xy(self.width - 1 - x, y));
+        self.mark_all_dirty()
+    }
+
+    /// Mirror the layer top-to-bottom in place. Only the cells are rearranged; the glyphs
+    /// themselves aren't mirrored. The cursor moves with its cell.
+    pub fn flip_v(&mut self) {
+        let Coord(w, h) = self.size();
+        for y in 0..(h / 2) {
+            for x in 0..w {
+                self.data.swap((x + y * w) as usize, (x + (h - 1 - y) * w) as usize)
+            }
+        }
+        self.cursor = self.cursor.map(|Coord(x, y)| xy(x, h - 1 - y));
+        self.mark_all_dirty()
+    }
+
+    /// Reset every cell to `Cell::default()`, a white space on `CLEAR`
+    pub fn clear(&mut self) {
+        self.clear_to(Cell::default())
+    }
+
+    /// Set every cell to the same cell
+    /// ```
+    /// # use heart437::*;
+    /// # let font = Font::default();
+    /// let mut layer = Layer::new(&font, xy(80, 25), pxy(1, 1), pxy(0, 0));
+    /// layer.clear_to(Fg(WHITE) + Bg(BLACK) + Char(b' '));
+    /// ```
+    pub fn clear_to(&mut self, cell: Cell) {
+        self.data.fill(cell);
+        self.mark_all_dirty()
+    }
+
+    /// Copy another layer's cells onto this one, with the other layer's top-left cell at `at`,
+    /// clipped to this layer's edges. Transparency works like drawing one layer over the other:
+    /// a cell with a transparent background keeps this layer's background, and only its char and
+    /// foreground are copied; if it also has a transparent foreground or a blank glyph (like a
+    /// `CLEAR` space) there's nothing to see, so it's skipped entirely.
+    /// ```
+    /// # use heart437::*;
+    /// # let font = Font::default();
+    /// let mut map = Layer::new(&font, xy(80, 25), pxy(1, 1), pxy(0, 0));
+    /// let mut ui = Layer::new(&font, xy(20, 3), pxy(1, 1), pxy(0, 0));
+    /// ui.print(xy(0, 0), "Score: 10", Some(YELLOW), None);
+    /// map.compose(&ui, xy(60, 0));
+    /// ```
+    pub fn compose(&mut self, other: &Layer, at: Coord) {
+        for pt in other.size() {
+            let dest = at + pt;
+            if !self.contains(dest) { continue }
+            let cell = other[pt];
+            if !cell.bg.is_transparent() {
+                self[dest] = cell
+            } else if !cell.fg.is_transparent() && !other.font[cell.ch].is_blank() {
+                self[dest] = Cell { bg: self[dest].bg, ..cell }
+            }
+        }
+    }
+
+    /// Highlight a cell as a cursor: it's drawn with its foreground and background swapped,
+    /// without changing the cell itself. Moving the cursor restores the old cell's colors, and
+    /// `None` removes it.
+    /// ```
+    /// # use heart437::*;
+    /// # let font = Font::default();
+    /// let mut layer = Layer::new(&font, xy(10, 10), pxy(1, 1), pxy(0, 0));
+    /// layer.set_cursor(Some(xy(3, 4)));
+    /// ```
+    pub fn set_cursor(&mut self, at: Option<Coord>) {
+        for pt in [self.cursor, at].into_iter().flatten() {
+            self.mark_dirty(pt)
+        }
+        self.cursor = at
+    }
+
+    /// Where the cursor is, if anywhere; see `set_cursor`
+    pub fn cursor(&self) -> Option<Coord> {
+        self.cursor
+    }
+
+    /// The cell as it should be drawn, which can differ from what's stored (such as the cursor)
+    fn display_cell(&self, pt: Coord) -> Cell {
+        let cell = self[pt];
+        if self.cursor == Some(pt) {
+            cell.reversed()
+        } else {
+            cell
+        }
+    }
+
+    /// The glyph and fg / bg colors a cell is actually drawn with, once its `Style` is applied
+    fn styled(&self, cell: Cell) -> (Glyph, Color, Color) {
+        let mut glyph = self.font[cell.ch];
+        if cell.style.contains(Style::BOLD) { glyph = glyph.bold() }
+        if cell.style.contains(Style::UNDERLINE) { glyph = glyph.underlined() }
+        if cell.style.contains(Style::REVERSE) {
+            (glyph, cell.bg, cell.fg)
+        } else {
+            (glyph, cell.fg, cell.bg)
         }
     }
 
@@ -68,39 +297,205 @@ impl<'a> Layer<'a> {
         pxy(px, py)
     }
 
+    /// The inverse of `pixel_coord`: returns the `Coord` of the cell drawn at a given pixel, such
+    /// as the mouse position, or `None` if that pixel isn't over the layer.
+    /// ```
+    /// # use heart437::*;
+    /// # let font = Font::default();
+    /// let layer = Layer::new(&font, xy(10, 10), pxy(2, 2), pxy(50, 50));
+    /// assert_eq!(layer.cell_at_pixel(pxy(67, 50)), Some(xy(1, 0)));
+    /// assert_eq!(layer.cell_at_pixel(pxy(49, 50)), None);
+    /// ```
+    pub fn cell_at_pixel(&self, p: PixelCoord) -> Option<Coord> {
+        let (scalex, scaley) = (self.scale.0.max(1), self.scale.1.max(1));
+        let x = (p.0 - self.origin.0).div_euclid(8 * scalex);
+        let y = (p.1 - self.origin.1).div_euclid(8 * scaley);
+        Some(xy(x, y)).filter(|c| self.contains(*c))
+    }
+
+    /// Save the layer's cells to a compact binary format, which `deserialize` can read back. The
+    /// format is a magic header (`H437`), a version byte, the width and height as little-endian
+    /// u32s, and then each cell in reading order in the layout of `Cell::to_bytes`.
+    /// The font, scale, origin, and cursor aren't saved, and neither are cells' blink and style.
+    /// ```
+    /// # use heart437::*;
+    /// # let font = Font::default();
+    /// let mut layer = Layer::new(&font, xy(80, 25), pxy(1, 1), pxy(0, 0));
+    /// layer[xy(3, 3)] |= Fg(RED) + Char(b'@');
+    /// let saved = layer.serialize();
+    /// let loaded = Layer::deserialize(&font, &saved).unwrap();
+    /// assert_eq!(loaded[xy(3, 3)], layer[xy(3, 3)]);
+    /// ```
+    pub fn serialize(&self) -> Vec<u8> {
+        let Coord(width, height) = self.size();
+        let mut bytes = Vec::with_capacity(LAYER_HEADER_LEN + self.data.len() * 9);
+        bytes.extend_from_slice(LAYER_MAGIC);
+        bytes.push(LAYER_VERSION);
+        bytes.extend_from_slice(&(width as u32).to_le_bytes());
+        bytes.extend_from_slice(&(height as u32).to_le_bytes());
+        for cell in self.data.iter() {
+            bytes.extend_from_slice(&cell.to_bytes());
+        }
+        bytes
+    }
+
+    /// Load a layer saved with `serialize`, using a given font. The new layer has a 1x scale and
+    /// is at the origin.
+    pub fn deserialize(font: &'a Font, bytes: &[u8]) -> Result<Self, LayerFormatError> {
+        if bytes.len() < LAYER_HEADER_LEN || &bytes[0..4] != LAYER_MAGIC {
+            return Err(LayerFormatError::BadHeader)
+        }
+        if bytes[4] != LAYER_VERSION {
+            return Err(LayerFormatError::UnsupportedVersion(bytes[4]))
+        }
+
+        let dimension = |start: usize| u32::from_le_bytes(bytes[start .. start + 4].try_into().unwrap());
+        let (width, height) = (dimension(5), dimension(9));
+        if width == 0 || height == 0 || width > i32::MAX as u32 || height > i32::MAX as u32 {
+            return Err(LayerFormatError::BadSize)
+        }
+        let cells = &bytes[LAYER_HEADER_LEN ..];
+        if cells.len() as u64 != width as u64 * height as u64 * 9 {
+            return Err(LayerFormatError::BadLength)
+        }
+
+        let mut layer = Layer::new(font, xy(width as i32, height as i32), pxy(1, 1), pxy(0, 0));
+        for (cell, b) in layer.data.iter_mut().zip(cells.chunks(9)) {
+            *cell = Cell::from_bytes(b.try_into().unwrap());
+        }
+        Ok(layer)
+    }
+
     /// Create a new VecGrid<Char> with the characters (uncolored) from this Layer
     pub fn chars(&self) -> VecGrid<Char> {
         let v = self.data.iter().map(|c| Char::from(*c));
         VecGrid::from_vec(v.collect(), self.width as usize, Char(' ' as u8))
     }
 
+    /// Read the text back out of a rectangle of the layer, the inverse of printing into it: one
+    /// line per row, with trailing spaces trimmed from each. Each cell's glyph is turned back into
+    /// a char through the font's codepage; slots the codepage has no char for come out as U+FFFD.
+    /// Parts of the rectangle outside the layer read as spaces.
+    /// ```
+    /// # use heart437::*;
+    /// # let font = Font::default();
+    /// let mut layer = Layer::new(&font, xy(20, 5), pxy(1, 1), pxy(0, 0));
+    /// layer.print(xy(2, 1), "Hello\nworld!", None, None);
+    /// assert_eq!(layer.get_text(xy(2, 1), xy(4, 2)), "Hell\nworl");
+    /// ```
+    pub fn get_text(&self, pos: Coord, size: Coord) -> String {
+        let codepage = self.font.codepage();
+        let rows: Vec<String> = (0..size.1).map(|y| {
+            let row: String = (0..size.0).map(|x| match self.get(pos + xy(x, y)) {
+                Some(cell) => codepage.char_at(cell.ch).unwrap_or(char::REPLACEMENT_CHARACTER),
+                None => ' '
+            }).collect();
+            row.trim_end_matches(' ').to_string()
+        }).collect();
+        rows.join("\n")
+    }
+
+    /// Fill the layer from a map of walls: every cell that's true in the map becomes `wall`, and
+    /// every cell that's false becomes `floor`. If the map and layer are different sizes, only the
+    /// cells in both are set.
+    /// ```
+    /// # use heart437::*;
+    /// # let font = Font::default();
+    /// let map = VecGrid::new(xy(10, 10), false);
+    /// let mut layer = Layer::new(&font, map.size(), pxy(1, 1), pxy(0, 0));
+    /// layer.render_map(&map, Fg(WHITE) + Bg(BLUE) + Char(b'#'), Fg(YELLOW) + Bg(BLACK) + Char(b'.'));
+    /// ```
+    pub fn render_map(&mut self, map: &VecGrid<bool>, wall: Cell, floor: Cell) {
+        for pt in self.size() {
+            if let Some(is_wall) = map.get(pt) {
+                self[pt] |= if *is_wall { wall } else { floor }
+            }
+        }
+    }
+
+    /// Turn the walls of a map into connected single-line walls: every cell for which `is_wall`
+    /// returns true gets the code page 437 line-drawing char (straight, corner, tee, or cross)
+    /// that joins up with its orthogonally-adjacent walls, and optionally a new color.
+    /// ```
+    /// # use heart437::*;
+    /// # let font = Font::default();
+    /// let map = VecGrid::from("####\n#..#\n####");
+    /// let mut layer = Layer::new(&font, map.size(), pxy(1, 1), pxy(0, 0));
+    /// layer.autotile_walls(|pt| map.get(pt) == Some(&'#'), Some(WHITE), None);
+    /// assert_eq!(layer[xy(0, 0)].ch, 0xda);
+    /// ```
+    pub fn autotile_walls<F: Fn(Coord) -> bool>(&mut self, is_wall: F, fg: Option<Color>, bg: Option<Color>) {
+        for pt in self.size() {
+            if is_wall(pt) {
+                let piece = wall_piece(is_wall(pt.north()), is_wall(pt.south()), is_wall(pt.east()), is_wall(pt.west()));
+                let cell = &mut self[pt];
+                *cell |= Char(piece);
+                if let Some(fg) = fg { *cell |= Fg(fg) }
+                if let Some(bg) = bg { *cell |= Bg(bg) }
+            }
+        }
+    }
+
+    /// The fg and bg colors to actually draw, with the layer's opacity applied. `None` if the
+    /// layer's invisible, so there's nothing to draw.
+    fn faded(&self, fg: Color, bg: Color) -> Option<(Color, Color)> {
+        let opacity = self.opacity.clamp(0.0, 1.0);
+        if opacity <= 0.0 {
+            None
+        } else if opacity >= 1.0 {
+            Some((fg, bg))
+        } else {
+            Some((fg.faded(opacity), bg.faded(opacity)))
+        }
+    }
+
     fn blit(&self, pixels: &mut [u8], width: usize, glyph: Glyph, fg: Color, bg: Color, pc: PixelCoord, scale: PixelCoord) {
-        let PixelCoord(x, y) = pc;
+        let Some((fg, bg)) = self.faded(fg, bg) else { return };
         let PixelCoord(xscale, yscale) = scale;
-        let height = (pixels.len() / 4) / width; // Height of the pixel buffer in pixels
-
-        if x >= width as i32 || y >= height as i32 { return }
-        let (right, bottom) = (x + xscale * 8, y + yscale * 8);
-        if right < 0 || bottom < 0 { return }
-
-        for (color, xo, yo) in &glyph {
-            // Scaling is like drawing a tiny rectangle instead of a single pixel, for each dot:
-            for sy in 0..yscale {
-                for sx in 0..xscale {
-                    // Pixel coords of the current pixel:
-                    let (px, py) = (x + xscale * xo as i32 + sx, y + yscale * yo as i32 + sy);
-
-                    // If in bounds:
-                    if px < width as i32 && py < height as i32 && px >= 0 && py >= 0 {
-                        let (px, py) = (px as usize, py as usize);
-                        let start = px * 4 + py * width * 4; // byte addr of start of pixel
-                        let current = &mut pixels[start .. (start + 4)];
-                        let new = (if color { fg } else { bg }).blend_into(current);
-                        for n in 0..4 { current[n] = new[n] }
+        let scaled = self.scaled.get(glyph, xscale, yscale);
+        stamp(pixels, width, None, &scaled, xscale, (fg, bg), pc)
+    }
+
+    /// Draws a string straight into the pixel buffer, packing the glyphs together by their
+    /// `Glyph::advance` rather than a fixed 8 pixels each, for a pseudo-proportional look. Like
+    /// sprites, the position is an offset from the layer's origin, and the text is scaled by the
+    /// layer's scale. Returns how many pixels wide the text was.
+    /// Chars are looked up in the font's `Codepage`, like `Canvas::print`; any that aren't in it
+    /// are drawn as `?`.
+    /// ```
+    /// # use heart437::*;
+    /// # let font = Font::default();
+    /// let layer = Layer::new(&font, xy(10, 10), pxy(1, 1), pxy(0, 0));
+    /// let mut buf = [0u8; (80 * 80 * 4)];
+    /// let width = layer.print_proportional(&mut buf, 80, pxy(0, 0), "Hello", WHITE, CLEAR);
+    /// ```
+    pub fn print_proportional(&self, pixels: &mut [u8], width: usize, at: PixelCoord, text: &str, fg: Color, bg: Color) -> i32 {
+        let PixelCoord(xscale, yscale) = PixelCoord(self.scale.0.max(1), self.scale.1.max(1));
+        let PixelCoord(x, y) = at + self.origin;
+        let height = (pixels.len() / 4) / width;
+        let mut pen = 0;
+        let colors = self.faded(fg, bg);
+
+        for ch in text.chars() {
+            let glyph = self.font[self.font.slot_or_fallback(ch)];
+            let first = glyph.used_cols().map_or(0, |(first, _)| first);
+            let advance = glyph.advance();
+
+            // Only draw the columns this glyph uses, so its background doesn't cover its neighbors:
+            if let Some((fg, bg)) = colors {
+                for (color, xo, yo) in glyph.into_iter().filter(|(_, xo, _)| *xo >= first && *xo < first + advance) {
+                    for sy in 0..yscale {
+                        for sx in 0..xscale {
+                            let (px, py) = (x + xscale * (pen + xo - first) as i32 + sx, y + yscale * yo as i32 + sy);
+                            plot(pixels, width, height, px, py, if color { fg } else { bg })
+                        }
                     }
                 }
             }
+            pen += advance;
         }
+
+        pen as i32 * xscale
     }
 
     /// Draws the Layer into the frame at its pixel position:
@@ -113,10 +508,206 @@ impl<'a> Layer<'a> {
     /// layer.draw(&mut buf, 640);
     /// ```
     pub fn draw(&self, pixels: &mut [u8], width: usize) {
+        self.draw_shaken(pixels, width, pxy(0, 0))
+    }
+
+    /// Draws the Layer as though its origin were moved by some offset, for screen-shake effects.
+    /// Drive the offset from a timer in your game loop, rather than changing `origin` every frame.
+    /// Anything shaken past the edges of the pixel buffer is clipped.
+    /// With the `rayon` feature, this (and so `draw`) splits the buffer into horizontal bands and
+    /// draws them in parallel, with the same result.
+    pub fn draw_shaken(&self, pixels: &mut [u8], width: usize, offset: PixelCoord) {
+        let scale = PixelCoord(self.scale.0.max(1), self.scale.1.max(1));
+
+        #[cfg(feature = "rayon")]
+        self.draw_bands(pixels, width, offset, scale);
+
+        #[cfg(not(feature = "rayon"))]
+        for (glyph, fg, bg, pc) in self.cells() {
+            self.blit(pixels, width, glyph, fg, bg, pc + offset, scale)
+        }
+    }
+
+    /// The parallel half of `draw_shaken`. Every cell is prepared up front, and then each band
+    /// draws the cells that overlap it, in the same order a single thread would, so cells that
+    /// overlap (from a shake or an odd origin) blend the same either way.
+    #[cfg(feature = "rayon")]
+    fn draw_bands(&self, pixels: &mut [u8], width: usize, offset: PixelCoord, scale: PixelCoord) {
+        use rayon::prelude::*;
+
+        let cell_height = scale.1 * 8;
+        let cells: Vec<_> = self.cells().filter_map(|(glyph, fg, bg, pc)| {
+            let (fg, bg) = self.faded(fg, bg)?;
+            Some((self.scaled.get(glyph, scale.0, scale.1), fg, bg, pc + offset))
+        }).collect();
+
+        let height = (pixels.len() / 4) / width;
+        let band_height = height.div_ceil(rayon::current_num_threads() * 4).max(1);
+        pixels.par_chunks_mut(band_height * width * 4).enumerate().for_each(|(n, band)| {
+            let top = (n * band_height) as i32;
+            let bottom = top + (band.len() / 4 / width) as i32;
+            for (scaled, fg, bg, pc) in &cells {
+                if pc.1 < bottom && pc.1 + cell_height > top {
+                    stamp(band, width, None, scaled, scale.0, (*fg, *bg), *pc + pxy(0, -top))
+                }
+            }
+        });
+    }
+
+    /// Draws the Layer like `draw` does, but only touches the pixels inside a rectangle of the
+    /// buffer, for panels that scroll inside a container without bleeding past its edges.
+    /// ```
+    /// # use heart437::*;
+    /// # let font = Font::default();
+    /// let mut list = Layer::new(&font, xy(20, 100), pxy(1, 1), pxy(16, 0));
+    /// # let scroll = 4;
+    /// list.origin = pxy(16, 16 - scroll * 8);
+    /// let mut buf = [0u8; (320 * 200 * 4)];
+    /// list.draw_clipped(&mut buf, 320, pxy(16, 16), pxy(160, 80));
+    /// ```
+    pub fn draw_clipped(&self, pixels: &mut [u8], width: usize, clip_pos: PixelCoord, clip_size: PixelCoord) {
         let scale = PixelCoord(self.scale.0.max(1), self.scale.1.max(1));
 
         for (glyph, fg, bg, pc) in self.cells() {
-            self.blit(pixels, width, glyph, fg, bg, pc, scale)
+            let Some(colors) = self.faded(fg, bg) else { continue };
+            let scaled = self.scaled.get(glyph, scale.0, scale.1);
+            stamp(pixels, width, Some((clip_pos, clip_size)), &scaled, scale.0, colors, pc)
+        }
+    }
+
+    /// Draws the Layer with its blinking cells in one phase of their blink: when `phase` is true
+    /// they're drawn normally, like `draw` does, and when it's false their foreground is drawn in
+    /// the background color, hiding the glyph. Flip the phase from a timer in your game loop.
+    /// ```
+    /// # use heart437::*;
+    /// # let font = Font::default();
+    /// let mut layer = Layer::new(&font, xy(10, 10), pxy(1, 1), pxy(0, 0));
+    /// layer[xy(0, 0)] = Fg(RED) + Bg(BLACK) + Char(b'!') + Blink(true);
+    /// let mut buf = [0u8; (80 * 80 * 4)];
+    /// # let frame = 0;
+    /// layer.draw_at_phase(&mut buf, 80, frame % 60 < 30);
+    /// ```
+    pub fn draw_at_phase(&self, pixels: &mut [u8], width: usize, phase: bool) {
+        let scale = PixelCoord(self.scale.0.max(1), self.scale.1.max(1));
+
+        for pt in self.size() {
+            let cell = self.display_cell(pt);
+            let (glyph, fg, bg) = self.styled(cell);
+            let fg = if cell.blink && !phase { bg } else { fg };
+            self.blit(pixels, width, glyph, fg, bg, self.pixel_coord(pt), scale)
+        }
+    }
+
+    /// Another name for `draw_at_phase`: blinking cells are drawn as blank background when
+    /// `blink_on` is false, so the game loop can toggle it on a timer.
+    /// ```
+    /// # use heart437::*;
+    /// # let font = Font::default();
+    /// # let layer = Layer::new(&font, xy(10, 10), pxy(1, 1), pxy(0, 0));
+    /// # let mut buf = [0u8; (80 * 80 * 4)];
+    /// # let frame = 0;
+    /// layer.draw_blinking(&mut buf, 80, frame % 60 < 30);
+    /// ```
+    pub fn draw_blinking(&self, pixels: &mut [u8], width: usize, blink_on: bool) {
+        self.draw_at_phase(pixels, width, blink_on)
+    }
+
+    /// Draws a window into some other grid of cells instead of this layer's own, with `camera` as
+    /// the world coordinate shown in the layer's top-left cell. The layer only provides the size,
+    /// font, scale, origin, and opacity; its own cells (and cursor) are ignored. Parts of the
+    /// window outside the world are drawn as `Cell::default()`.
+    /// ```
+    /// # use heart437::*;
+    /// # let font = Font::default();
+    /// let world = VecGrid::new(xy(500, 500), Fg(WHITE) + Bg(BLACK) + Char(b'.'));
+    /// let screen = Layer::new(&font, xy(10, 10), pxy(1, 1), pxy(0, 0));
+    /// let mut buf = [0u8; (80 * 80 * 4)];
+    /// # let player = xy(250, 250);
+    /// screen.draw_grid(&world, player + xy(-5, -5), &mut buf, 80);
+    /// ```
+    pub fn draw_grid<G: Grid<CellType=Cell>>(&self, world: &G, camera: Coord, pixels: &mut [u8], width: usize) {
+        let scale = PixelCoord(self.scale.0.max(1), self.scale.1.max(1));
+
+        for pt in self.size() {
+            let cell = world.get(camera + pt).copied().unwrap_or_default();
+            let (glyph, fg, bg) = self.styled(cell);
+            self.blit(pixels, width, glyph, fg, bg, self.pixel_coord(pt), scale)
+        }
+    }
+
+    /// Draws only the cells that have changed since the last `draw_dirty`, for redrawing a
+    /// mostly-static layer cheaply into a pixel buffer that's kept between frames. A cell is
+    /// dirty when it's borrowed mutably (such as through `IndexMut`, or by anything that draws on
+    /// the layer) or the cursor moves onto or off it. A new layer starts out all dirty.
+    /// Changes that don't touch the cells, like moving the layer or changing its `opacity`, aren't
+    /// tracked; use `mark_all_dirty` after those, or if the pixel buffer was cleared or reallocated.
+    /// ```
+    /// # use heart437::*;
+    /// # let font = Font::default();
+    /// let mut layer = Layer::new(&font, xy(10, 10), pxy(1, 1), pxy(0, 0));
+    /// let mut buf = [0u8; (80 * 80 * 4)];
+    /// layer.draw_dirty(&mut buf, 80); // Draws everything
+    /// layer[xy(3, 3)] |= Char(b'@');
+    /// layer.draw_dirty(&mut buf, 80); // Draws only (3, 3)
+    /// ```
+    pub fn draw_dirty(&mut self, pixels: &mut [u8], width: usize) {
+        let scale = PixelCoord(self.scale.0.max(1), self.scale.1.max(1));
+
+        for (n, pt) in self.size().into_iter().enumerate() {
+            if std::mem::replace(&mut self.dirty[n], false) {
+                let (glyph, fg, bg) = self.styled(self.display_cell(pt));
+                self.blit(pixels, width, glyph, fg, bg, self.pixel_coord(pt), scale)
+            }
+        }
+    }
+
+    /// Mark every cell as changed, so the next `draw_dirty` redraws the whole layer
+    pub fn mark_all_dirty(&mut self) {
+        self.dirty.fill(true)
+    }
+
+    fn mark_dirty(&mut self, pt: Coord) {
+        if self.contains(pt) {
+            self.dirty[(pt.0 + self.width * pt.1) as usize] = true
+        }
+    }
+
+    /// Draws only the cells of the Layer whose coordinate is `true` in a mask, such as the output of
+    /// a field-of-view calculation. Cells outside the mask (or false in it) are skipped, leaving
+    /// whatever was already in the pixel buffer.
+    /// ```
+    /// # use heart437::*;
+    /// # let font = Font::default();
+    /// let layer = Layer::new(&font, xy(10, 10), pxy(1, 1), pxy(0, 0));
+    /// let mut visible = VecGrid::new(xy(10, 10), false);
+    /// visible[xy(5, 5)] = true;
+    /// let mut buf = [0u8; (80 * 80 * 4)];
+    /// layer.draw_masked(&mut buf, 80, &visible);
+    /// ```
+    pub fn draw_masked(&self, pixels: &mut [u8], width: usize, visible: &VecGrid<bool>) {
+        let scale = PixelCoord(self.scale.0.max(1), self.scale.1.max(1));
+
+        for pt in self.size() {
+            if *visible.get(pt).unwrap_or(&false) {
+                let (glyph, fg, bg) = self.styled(self.display_cell(pt));
+                self.blit(pixels, width, glyph, fg, bg, self.pixel_coord(pt), scale)
+            }
+        }
+    }
+
+    /// Draws the Layer with each cell's colors multiplied by a light level from 0..1, taken from the
+    /// same coordinate in a grid of intensities. Cells with no light (or outside the light grid)
+    /// are skipped entirely, leaving whatever was already in the pixel buffer.
+    pub fn draw_with_light(&self, pixels: &mut [u8], width: usize, light: &VecGrid<f32>) {
+        let scale = PixelCoord(self.scale.0.max(1), self.scale.1.max(1));
+
+        for pt in self.size() {
+            let intensity = *light.get(pt).unwrap_or(&0.0);
+            if intensity > 0.0 {
+                let (glyph, fg, bg) = self.styled(self.display_cell(pt));
+                let (fg, bg) = (fg.dimmed(intensity), bg.dimmed(intensity));
+                self.blit(pixels, width, glyph, fg, bg, self.pixel_coord(pt), scale)
+            }
         }
     }
 
@@ -127,14 +718,112 @@ impl<'a> Layer<'a> {
     /// which is treeated as an offset from the layer's position: if the layer has position (50, 50)
     /// and a sprite has position (25, 25), the sprite will actually be drawn at (75, 75). In other
     /// words, moving the layer also moves the sprites it draws.
+    /// Sprites with `visible` set to false aren't drawn.
     pub fn draw_sprites<'b, I: Iterator<Item=&'b Sprite>, II: IntoIterator<IntoIter=I>>(&self, sprites: II, pixels: &mut [u8], width: usize) {
-        for sprite in sprites {
-            let Cell { ch, fg, bg} = sprite.cell;
-            let glyph = self.font[ch];
+        for sprite in sprites.into_iter().filter(|s| s.visible) {
+            let (glyph, fg, bg) = self.styled(sprite.cell);
             let scale = PixelCoord(sprite.scale.0.max(1), sprite.scale.1.max(1));
             self.blit(pixels, width, glyph, fg, bg, sprite.position + self.origin, scale)
         }
     }
+
+    /// Renders the layer into a new image just big enough to hold it, for screenshots and other
+    /// drawing without a window. The layer's scale is used but its origin isn't: the layer's
+    /// top-left is at the image's top-left. Pixels the layer doesn't cover are transparent.
+    /// ```
+    /// # use heart437::*;
+    /// # let font = Font::default();
+    /// let layer = Layer::new(&font, xy(10, 5), pxy(2, 1), pxy(50, 50));
+    /// let image = layer.to_image();
+    /// assert_eq!(image.dimensions(), (160, 40));
+    /// ```
+    pub fn to_image(&self) -> image::RgbaImage {
+        let Coord(width, height) = self.size();
+        let scale = PixelCoord(self.scale.0.max(1), self.scale.1.max(1));
+        let (width, height) = ((width * 8 * scale.0) as u32, (height * 8 * scale.1) as u32);
+        let mut pixels = vec![0u8; (width * height * 4) as usize];
+        self.draw_shaken(&mut pixels, width as usize, pxy(-self.origin.0, -self.origin.1));
+        image::RgbaImage::from_raw(width, height, pixels).expect("Buffer is sized for the image")
+    }
+
+    /// Renders the layer with `to_image` and saves it as a PNG file.
+    pub fn save_png<P: AsRef<std::path::Path>>(&self, path: P) -> image::ImageResult<()> {
+        self.to_image().save_with_format(path, image::ImageFormat::Png)
+    }
+}
+
+const LAYER_MAGIC: &[u8; 4] = b"H437";
+const LAYER_VERSION: u8 = 1;
+const LAYER_HEADER_LEN: usize = 13;
+
+/// The ways `Layer::deserialize` can fail
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LayerFormatError {
+    /// The data doesn't start with the magic header, so it's not a saved layer
+    BadHeader,
+    /// The data was saved by a different version of the format
+    UnsupportedVersion(u8),
+    /// The width or height is zero, or too large
+    BadSize,
+    /// The amount of cell data doesn't match the width and height
+    BadLength
+}
+
+impl Display for LayerFormatError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LayerFormatError::BadHeader => write!(f, "not a saved layer"),
+            LayerFormatError::UnsupportedVersion(v) => write!(f, "unsupported layer format version {}", v),
+            LayerFormatError::BadSize => write!(f, "invalid layer dimensions"),
+            LayerFormatError::BadLength => write!(f, "layer data is the wrong length for its dimensions"),
+        }
+    }
+}
+
+impl std::error::Error for LayerFormatError {}
+
+/// Blend a color into a single pixel of a pixel buffer, if that pixel is in bounds. Fully
+/// transparent colors leave the pixel untouched.
+fn plot(pixels: &mut [u8], width: usize, height: usize, px: i32, py: i32, color: Color) {
+    if color.is_transparent() { return }
+    if px < width as i32 && py < height as i32 && px >= 0 && py >= 0 {
+        let (px, py) = (px as usize, py as usize);
+        let start = px * 4 + py * width * 4; // byte addr of start of pixel
+        let current = &mut pixels[start .. (start + 4)];
+        let new = color.blend_into(current);
+        for n in 0..4 { current[n] = new[n] }
+    }
+}
+
+/// Draw a glyph that's already been expanded to its scale (by `ScaledGlyphs`) with its top-left
+/// at `pc`, clipped to the pixel buffer, and to a `(pos, size)` rectangle within it if one's given
+fn stamp(pixels: &mut [u8], width: usize, clip: Option<(PixelCoord, PixelCoord)>, scaled: &[bool], xscale: i32, colors: (Color, Color), pc: PixelCoord) {
+    let PixelCoord(x, y) = pc;
+    let (fg, bg) = colors;
+    let glyph_width = (xscale * 8) as usize;
+    let height = (pixels.len() / 4) / width; // Height of the pixel buffer in pixels
+    let (mut left, mut top, mut right, mut bottom) = (0, 0, width as i32, height as i32);
+    if let Some((PixelCoord(cx, cy), PixelCoord(cw, ch))) = clip {
+        (left, top) = (left.max(cx), top.max(cy));
+        (right, bottom) = (right.min(cx + cw), bottom.min(cy + ch));
+    }
+
+    // Only the columns of the glyph that land in the clip get drawn:
+    let (first, last) = ((left - x).max(0), (glyph_width as i32).min(right - x));
+    if first >= last { return }
+    let (first, last) = (first as usize, last as usize);
+
+    for (row, py) in scaled.chunks(glyph_width).zip(y..) {
+        if py < top || py >= bottom { continue }
+        let start = (py as usize * width + (x + first as i32) as usize) * 4; // byte addr of first pixel
+        let dest = &mut pixels[start .. (start + (last - first) * 4)];
+        for (&on, current) in row[first..last].iter().zip(dest.chunks_mut(4)) {
+            let color = if on { fg } else { bg };
+            if color.is_transparent() { continue }
+            let new = color.blend_into(current);
+            current.copy_from_slice(&new)
+        }
+    }
 }
 
 impl Grid for Layer<'_> {
@@ -157,7 +846,9 @@ impl Grid for Layer<'_> {
 impl GridMut for Layer<'_> {
     fn get_mut(&mut self, index: Coord) -> Option<&mut Cell> {
         if self.contains(index) {
-            Some(&mut self.data[(index.0 + self.width * index.1) as usize])
+            let n = (index.0 + self.width * index.1) as usize;
+            self.dirty[n] = true;
+            Some(&mut self.data[n])
         } else {
             None
         }
@@ -187,9 +878,8 @@ impl Iterator for CharIterator<'_> {
         let n = self.n;
         self.n += 1;
         let coord = self.layer.coord(n);
-        if let Some(c) = self.layer.get(coord) {
-            let Cell { ch, fg, bg } = *c;
-            let glyph = self.layer.font[ch];
+        if self.layer.contains(coord) {
+            let (glyph, fg, bg) = self.layer.styled(self.layer.display_cell(coord));
             Some((glyph, fg, bg, self.layer.pixel_coord(coord)))
         } else {
             None
@@ -199,7 +889,7 @@ impl Iterator for CharIterator<'_> {
 
 #[cfg(test)]
 mod test {
-    use crate::{Bg, Char, Fg, RED, YELLOW};
+    use crate::{Blink, CLEAR, RED, WHITE, YELLOW};
     use super::*;
 
     #[test]
@@ -247,4 +937,562 @@ mod test {
         layer[xy(3, 5)] |= Char('a' as u8);
         assert_eq!(layer.chars()[xy(3, 5)], Char('a' as u8));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_invisible_sprites() {
+        let font = Font::default();
+        let layer = Layer::new(&font, xy(2, 2), pxy(1, 1), pxy(0, 0));
+        let mut sprite = Sprite::from_char('@', WHITE, RED, pxy(0, 0));
+
+        let mut buf = [0u8; 16 * 16 * 4];
+        layer.draw_sprites([&sprite], &mut buf, 16);
+        assert!(buf.iter().any(|b| *b != 0));
+
+        sprite.visible = false;
+        let mut buf = [0u8; 16 * 16 * 4];
+        layer.draw_sprites([&sprite], &mut buf, 16);
+        assert!(buf.iter().all(|b| *b == 0));
+    }
+
+    #[test]
+    fn test_draw_masked() {
+        let font = Font::default();
+        let mut layer = Layer::new(&font, xy(3, 1), pxy(1, 1), pxy(0, 0));
+        for pt in layer.size() { layer[pt] |= Bg(RED) }
+        let mut mask = VecGrid::new(xy(3, 1), false);
+        mask[xy(1, 0)] = true;
+
+        let mut buf = [0u8; 24 * 8 * 4];
+        layer.draw_masked(&mut buf, 24, &mask);
+        // Only the middle cell, pixels 8..16 of each row, was drawn:
+        for (n, px) in buf.chunks(4).enumerate() {
+            let x = n % 24;
+            if (8..16).contains(&x) {
+                assert_eq!(px, [255, 0, 0, 255]);
+            } else {
+                assert_eq!(px, [0, 0, 0, 0]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_draw_at_phase() {
+        let font = Font::default();
+        let mut layer = Layer::new(&font, xy(2, 1), pxy(1, 1), pxy(0, 0));
+        layer[xy(0, 0)] = Fg(WHITE) + Bg(RED) + Char(b'#') + Blink(true);
+        layer[xy(1, 0)] = Fg(WHITE) + Bg(RED) + Char(b'#');
+        let white_in = |buf: &[u8], cell: usize| buf.chunks(4).enumerate()
+            .any(|(n, px)| n % 16 / 8 == cell && px == [255, 255, 255, 255]);
+
+        let mut on = [0u8; 16 * 8 * 4];
+        layer.draw_at_phase(&mut on, 16, true);
+        let mut plain = [0u8; 16 * 8 * 4];
+        layer.draw(&mut plain, 16);
+        assert_eq!(on, plain);
+        assert!(white_in(&on, 0) && white_in(&on, 1));
+
+        // Off phase hides only the blinking glyph:
+        let mut off = [0u8; 16 * 8 * 4];
+        layer.draw_at_phase(&mut off, 16, false);
+        assert!(!white_in(&off, 0) && white_in(&off, 1));
+    }
+
+    #[test]
+    fn test_draw_blinking() {
+        let font = Font::default();
+        let mut layer = Layer::new(&font, xy(1, 1), pxy(1, 1), pxy(0, 0));
+        layer[xy(0, 0)] = Fg(WHITE) + Bg(RED) + Char(b'#') + Blink(true);
+        let drawn = |blink_on| {
+            let mut buf = [0u8; 8 * 8 * 4];
+            layer.draw_blinking(&mut buf, 8, blink_on);
+            buf
+        };
+
+        assert_ne!(drawn(true), drawn(false));
+        assert!(drawn(false).chunks(4).all(|px| px == [255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn test_draw_styles() {
+        let font = Font::default();
+        let mut layer = Layer::new(&font, xy(1, 1), pxy(1, 1), pxy(0, 0));
+        let drawn = |layer: &Layer| {
+            let mut buf = [0u8; 8 * 8 * 4];
+            layer.draw(&mut buf, 8);
+            buf.chunks(4).map(|px| px == [255, 255, 255, 255]).collect::<Vec<bool>>()
+        };
+
+        // A reversed space is all foreground:
+        layer[xy(0, 0)] = Fg(WHITE) + Bg(RED) + Char(b' ') + Style::REVERSE;
+        assert!(drawn(&layer).iter().all(|on| *on));
+
+        // An underlined space has just the bottom row on:
+        layer[xy(0, 0)] |= Style::UNDERLINE;
+        let on = drawn(&layer);
+        assert!(on[56..].iter().all(|on| *on) && !on[..56].iter().any(|on| *on));
+
+        // Bold only ever adds pixels:
+        layer[xy(0, 0)] = Fg(WHITE) + Bg(RED) + Char(b'I');
+        let plain = drawn(&layer);
+        layer[xy(0, 0)] |= Style::BOLD;
+        let bold = drawn(&layer);
+        assert!(plain.iter().zip(bold.iter()).all(|(p, b)| !p || *b));
+        assert!(bold.iter().filter(|on| **on).count() > plain.iter().filter(|on| **on).count());
+    }
+
+    #[test]
+    fn test_set() {
+        let font = Font::default();
+        let mut layer = Layer::new(&font, xy(2, 2), pxy(1, 1), pxy(0, 0));
+        layer.set(xy(1, 1), OptionCell::new().with_ch(b'#').with_fg(RED));
+        assert_eq!(layer[xy(1, 1)], Fg(RED) + Bg(CLEAR) + Char(b'#'));
+        assert_eq!(layer[xy(0, 0)], Cell::default());
+
+        // The Canvas version funnels through the same thing:
+        crate::Canvas::set(&mut layer, xy(1, 1), None, None, Some(YELLOW));
+        assert_eq!(layer[xy(1, 1)], Fg(RED) + Bg(YELLOW) + Char(b'#'));
+    }
+
+    #[test]
+    fn test_resize() {
+        let font = Font::default();
+        let mut layer = Layer::new(&font, xy(3, 2), pxy(1, 1), pxy(0, 0));
+        for pt in layer.size() { layer[pt] |= Char(b'a' + (pt.0 + pt.1 * 3) as u8) }
+        layer.set_cursor(Some(xy(2, 1)));
+
+        layer.resize(xy(4, 3));
+        assert_eq!(layer.size(), xy(4, 3));
+        let text: String = layer.iter().map(|c| c.ch as char).collect();
+        assert_eq!(text, "abc def     ");
+        assert_eq!(layer.cursor(), Some(xy(2, 1)));
+
+        layer.resize(xy(2, 1));
+        assert_eq!(layer.size(), xy(2, 1));
+        assert_eq!(layer[xy(1, 0)].ch, b'b');
+        assert_eq!(layer.cursor(), None);
+    }
+
+    #[test]
+    fn test_send_sync() {
+        // Layers can be shared between threads, to draw them or read their cells:
+        fn is_send_sync<T: Send + Sync>() {}
+        is_send_sync::<Layer>();
+    }
+
+    #[test]
+    fn test_draw_dirty() {
+        let font = Font::default();
+        let mut layer = Layer::new(&font, xy(3, 1), pxy(1, 1), pxy(0, 0));
+        for pt in layer.size() { layer[pt] |= Bg(RED) }
+        let drawn = |layer: &mut Layer| {
+            let mut buf = [0u8; 24 * 8 * 4];
+            layer.draw_dirty(&mut buf, 24);
+            // Which cells touched the buffer at all:
+            (0..3).filter(|c| buf.chunks(4).enumerate().any(|(n, px)| n % 24 / 8 == *c && px[3] != 0)).collect::<Vec<usize>>()
+        };
+
+        assert_eq!(drawn(&mut layer), vec![0, 1, 2]);
+        assert_eq!(drawn(&mut layer), Vec::<usize>::new());
+        layer[xy(2, 0)] |= Char(b'x');
+        assert_eq!(drawn(&mut layer), vec![2]);
+        layer.set_cursor(Some(xy(0, 0)));
+        assert_eq!(drawn(&mut layer), vec![0]);
+        layer.set_cursor(Some(xy(1, 0)));
+        assert_eq!(drawn(&mut layer), vec![0, 1]);
+        layer.mark_all_dirty();
+        assert_eq!(drawn(&mut layer), vec![0, 1, 2]);
+        layer.resize(xy(2, 1));
+        assert_eq!(drawn(&mut layer), vec![0, 1]);
+    }
+
+    #[test]
+    fn test_blit_region() {
+        let font = Font::default();
+        let mut src = Layer::new(&font, xy(4, 4), pxy(1, 1), pxy(0, 0));
+        for pt in src.size() { src[pt] |= Char(b'a' + (pt.0 + pt.1 * 4) as u8) }
+        let mut dest = Layer::new(&font, xy(3, 3), pxy(1, 1), pxy(0, 0));
+        let text = |layer: &Layer| layer.iter().map(|c| c.ch as char).collect::<String>();
+
+        dest.blit_region(&src, (xy(1, 1), xy(2, 2)), xy(0, 1));
+        assert_eq!(text(&dest), "   fg jk ");
+
+        // Source rect hanging off the source's bottom right, dest off the top left:
+        dest.clear();
+        dest.blit_region(&src, (xy(2, 2), xy(3, 3)), xy(-1, 0));
+        assert_eq!(text(&dest), "l  p     ");
+    }
+
+    #[test]
+    fn test_opacity() {
+        let font = Font::default();
+        let mut layer = Layer::new(&font, xy(1, 1), pxy(1, 1), pxy(0, 0));
+        layer[xy(0, 0)] = Fg(WHITE) + Bg(RED) + Char(b' ');
+        let drawn = |layer: &Layer| {
+            let mut buf = [0u8; 8 * 8 * 4];
+            for px in buf.chunks_mut(4) { px.copy_from_slice(&[0, 0, 255, 255]) }
+            layer.draw(&mut buf, 8);
+            layer.print_proportional(&mut buf, 8, pxy(0, 0), "#", RED, RED);
+            [buf[0], buf[1], buf[2], buf[3]]
+        };
+
+        assert_eq!(drawn(&layer), [255, 0, 0, 255]);
+        layer.opacity = 0.5;
+        let [r, g, b, a] = drawn(&layer);
+        assert!(r > 128 && b < 128 && b > 0 && g == 0 && a == 255);
+        layer.opacity = 0.0;
+        assert_eq!(drawn(&layer), [0, 0, 255, 255]);
+        layer.opacity = -3.0;
+        assert_eq!(drawn(&layer), [0, 0, 255, 255]);
+        layer.opacity = 7.0;
+        assert_eq!(drawn(&layer), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_rotated() {
+        let font = Font::default();
+        let mut layer = Layer::new(&font, xy(3, 2), pxy(2, 1), pxy(5, 5));
+        for pt in layer.size() { layer[pt] |= Char(b'a' + (pt.0 + pt.1 * 3) as u8) }
+        layer.set_cursor(Some(xy(2, 0)));
+        let text = |layer: &Layer| layer.iter().map(|c| c.ch as char).collect::<String>();
+
+        // abc      da      fed      cf
+        // def  ->  eb  ->  cba  ->  be
+        //          fc               ad
+        let cw = layer.rotated(1);
+        assert_eq!(cw.size(), xy(2, 3));
+        assert_eq!(text(&cw), "daebfc");
+        assert_eq!(cw.cursor(), Some(xy(1, 2)));
+        assert_eq!((cw.scale, cw.origin), (pxy(2, 1), pxy(5, 5)));
+        assert_eq!(text(&layer.rotated(2)), "fedcba");
+        assert_eq!(text(&layer.rotated(3)), "cfbead");
+        assert_eq!(text(&layer.rotated(4)), text(&layer));
+        assert_eq!(text(&cw.rotated(3)), text(&layer));
+    }
+
+    #[test]
+    fn test_draw_grid() {
+        let font = Font::default();
+        let screen = Layer::new(&font, xy(2, 1), pxy(1, 1), pxy(0, 0));
+        let mut world = VecGrid::new(xy(5, 5), Fg(WHITE) + Bg(RED) + Char(b' '));
+        world[xy(3, 3)] |= Bg(YELLOW);
+
+        let mut buf = [0u8; 16 * 8 * 4];
+        screen.draw_grid(&world, xy(3, 3), &mut buf, 16);
+        assert_eq!(&buf[0..4], &[255, 255, 0, 255]); // (3, 3) is yellow
+        assert_eq!(&buf[32..36], &[255, 0, 0, 255]); // (4, 3) is red
+
+        let mut buf = [0u8; 16 * 8 * 4];
+        screen.draw_grid(&world, xy(4, 4), &mut buf, 16);
+        assert_eq!(&buf[0..4], &[255, 0, 0, 255]);
+        assert_eq!(&buf[32..36], &[0, 0, 0, 0]); // Off the world, a default clear cell
+    }
+
+    #[test]
+    fn test_clear() {
+        let font = Font::default();
+        let mut layer = Layer::new(&font, xy(3, 2), pxy(1, 1), pxy(0, 0));
+        let wall = Fg(WHITE) + Bg(RED) + Char(b'#');
+        layer.clear_to(wall);
+        assert!(layer.iter().all(|c| *c == wall));
+        layer.clear();
+        assert!(layer.iter().all(|c| *c == Cell::default()));
+    }
+
+    #[test]
+    fn test_compose() {
+        let font = Font::default();
+        let mut bottom = Layer::new(&font, xy(4, 2), pxy(1, 1), pxy(0, 0));
+        for pt in bottom.size() { bottom[pt] = Fg(WHITE) + Bg(RED) + Char(b'.') }
+        let mut top = Layer::new(&font, xy(3, 2), pxy(1, 1), pxy(0, 0));
+        top[xy(0, 0)] = Fg(YELLOW) + Bg(WHITE) + Char(b'#'); // Opaque
+        top[xy(1, 0)] = Fg(YELLOW) + Bg(CLEAR) + Char(b'@'); // Just a glyph
+        top[xy(2, 0)] = Fg(CLEAR) + Bg(CLEAR) + Char(b'@'); // Invisible
+        // (the default cells in the bottom row are blank spaces on CLEAR)
+
+        bottom.compose(&top, xy(2, 0));
+        assert_eq!(bottom[xy(1, 0)], Fg(WHITE) + Bg(RED) + Char(b'.'));
+        assert_eq!(bottom[xy(2, 0)], Fg(YELLOW) + Bg(WHITE) + Char(b'#'));
+        assert_eq!(bottom[xy(3, 0)], Fg(YELLOW) + Bg(RED) + Char(b'@'));
+        assert_eq!(bottom[xy(2, 1)], Fg(WHITE) + Bg(RED) + Char(b'.'));
+
+        // Clipped off the top left, too:
+        bottom.compose(&top, xy(-1, -1));
+        assert_eq!(bottom[xy(0, 0)], Fg(WHITE) + Bg(RED) + Char(b'.'));
+    }
+
+    #[test]
+    fn test_draw_with_light() {
+        let font = Font::default();
+        let mut layer = Layer::new(&font, xy(3, 1), pxy(1, 1), pxy(0, 0));
+        for pt in layer.size() { layer[pt] |= Bg(WHITE) }
+        let light = VecGrid::from_vec(vec![1.0, 0.5, 0.0], 3, 0.0);
+
+        let mut buf = [0u8; 24 * 8 * 4];
+        layer.draw_with_light(&mut buf, 24, &light);
+        let pixel = |x: usize| &buf[x * 4 .. x * 4 + 4];
+        assert_eq!(pixel(0), [255, 255, 255, 255]);
+        assert_eq!(pixel(8), [127, 127, 127, 255]);
+        // The unlit cell is skipped:
+        assert_eq!(pixel(16), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_print_proportional() {
+        let font = Font::default();
+        let layer = Layer::new(&font, xy(10, 10), pxy(2, 1), pxy(0, 0));
+        let mut buf = [0u8; 80 * 8 * 4];
+
+        let narrow = layer.print_proportional(&mut buf, 80, pxy(0, 0), "il", WHITE, RED);
+        let wide = layer.print_proportional(&mut buf, 80, pxy(0, 0), "WM", WHITE, RED);
+        assert!(narrow < wide);
+        assert!(wide <= 2 * 8 * 2);
+
+        // Nothing past the end of the text was drawn:
+        let drawn = buf.chunks(4).take(80).filter(|px| px[3] != 0).count();
+        assert_eq!(drawn as i32, wide);
+
+        // Chars the codepage doesn't have print as '?':
+        let mut accented = [0u8; 80 * 8 * 4];
+        let mut plain = [0u8; 80 * 8 * 4];
+        layer.print_proportional(&mut accented, 80, pxy(0, 0), "né", WHITE, RED);
+        layer.print_proportional(&mut plain, 80, pxy(0, 0), "n?", WHITE, RED);
+        assert_eq!(accented, plain);
+    }
+
+    #[test]
+    fn test_draw_shaken() {
+        let font = Font::default();
+        let mut layer = Layer::new(&font, xy(1, 1), pxy(1, 1), pxy(4, 4));
+        layer[xy(0, 0)] |= Bg(RED);
+
+        let mut still = [0u8; 16 * 16 * 4];
+        layer.draw(&mut still, 16);
+        let mut shaken = [0u8; 16 * 16 * 4];
+        layer.draw_shaken(&mut shaken, 16, pxy(-3, 2));
+
+        let red_pixels = |buf: &[u8]| buf.chunks(4).enumerate()
+            .filter(|(_, px)| px[0] == 255)
+            .map(|(n, _)| pxy(n as i32 % 16, n as i32 / 16))
+            .collect::<Vec<_>>();
+        let moved: Vec<PixelCoord> = red_pixels(&still).into_iter().map(|p| p + pxy(-3, 2)).collect();
+        assert_eq!(red_pixels(&shaken), moved);
+
+        // Shaking off the edge clips instead of panicking:
+        layer.draw_shaken(&mut shaken, 16, pxy(-10, 20));
+    }
+
+    #[test]
+    fn test_autotile_walls() {
+        let font = Font::default();
+        let map = VecGrid::from("#..\n\
+                                 #..\n\
+                                 ###");
+        let mut layer = Layer::new(&font, map.size(), pxy(1, 1), pxy(0, 0));
+        layer.autotile_walls(|pt| map.get(pt) == Some(&'#'), Some(YELLOW), None);
+
+        assert_eq!(layer[xy(0, 0)].ch, 0xb3); // Vertical
+        assert_eq!(layer[xy(0, 1)].ch, 0xb3);
+        assert_eq!(layer[xy(0, 2)].ch, 0xc0); // The corner of the L
+        assert_eq!(layer[xy(2, 2)].ch, 0xc4); // Horizontal
+        assert_eq!(layer[xy(0, 2)].fg, YELLOW);
+        assert_eq!(layer[xy(1, 1)], Cell::default()); // Floors are untouched
+    }
+
+    #[test]
+    fn test_render_map() {
+        let font = Font::default();
+        let map = VecGrid::from("#.\n.#\n##").map_grid(|_, ch| *ch == '#', false);
+        let mut layer = Layer::new(&font, xy(3, 2), pxy(1, 1), pxy(0, 0));
+        let wall = Fg(WHITE) + Bg(RED) + Char(b'#');
+        let floor = Fg(YELLOW) + Bg(WHITE) + Char(b'.');
+        layer.render_map(&map, wall, floor);
+
+        assert_eq!(layer[xy(0, 0)], wall);
+        assert_eq!(layer[xy(1, 0)], floor);
+        assert_eq!(layer[xy(0, 1)], floor);
+        assert_eq!(layer[xy(1, 1)], wall);
+        // Outside the map is left alone, and the map's last row is clipped off:
+        assert_eq!(layer[xy(2, 0)], Cell::default());
+    }
+
+    #[test]
+    fn test_cell_at_pixel() {
+        let font = Font::default();
+        let layer = Layer::new(&font, xy(10, 5), pxy(3, 2), pxy(20, 10));
+
+        // The second cell spans x = 20 + 24 .. 20 + 48:
+        assert_eq!(layer.cell_at_pixel(pxy(44, 10)), Some(xy(1, 0)));
+        assert_eq!(layer.cell_at_pixel(pxy(67, 25)), Some(xy(1, 0)));
+        assert_eq!(layer.cell_at_pixel(pxy(68, 26)), Some(xy(2, 1)));
+        for pt in layer.size() {
+            assert_eq!(layer.cell_at_pixel(layer.pixel_coord(pt)), Some(pt));
+        }
+
+        // Off the edges:
+        assert_eq!(layer.cell_at_pixel(pxy(19, 10)), None);
+        assert_eq!(layer.cell_at_pixel(pxy(20 + 240, 10)), None);
+        assert_eq!(layer.cell_at_pixel(pxy(20, 10 + 80)), None);
+
+        // A scale of 0 draws as 1, so it maps the same way:
+        let unscaled = Layer::new(&font, xy(10, 5), pxy(0, 0), pxy(0, 0));
+        assert_eq!(unscaled.cell_at_pixel(pxy(9, 0)), Some(xy(1, 0)));
+    }
+
+    #[test]
+    fn test_cursor() {
+        let font = Font::default();
+        let mut layer = Layer::new(&font, xy(3, 1), pxy(1, 1), pxy(0, 0));
+        for pt in layer.size() { layer[pt] |= Fg(YELLOW) + Bg(RED) }
+        layer.set_cursor(Some(xy(1, 0)));
+
+        let mut buf = [0u8; 24 * 8 * 4];
+        layer.draw(&mut buf, 24);
+        // Spaces are all background, so the cursor cell is drawn in the foreground color:
+        let pixel = |x: usize| &buf[x * 4 .. x * 4 + 4];
+        assert_eq!(pixel(4), [255, 0, 0, 255]);
+        assert_eq!(pixel(12), [255, 255, 0, 255]);
+        assert_eq!(pixel(20), [255, 0, 0, 255]);
+        // And the cell itself is unchanged:
+        assert_eq!(layer[xy(1, 0)].bg, RED);
+
+        layer.set_cursor(None);
+        layer.draw(&mut buf, 24);
+        assert_eq!(&buf[12 * 4 .. 12 * 4 + 4], [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_serialize() {
+        let font = Font::default();
+        let mut layer = Layer::new(&font, xy(4, 3), pxy(2, 2), pxy(10, 10));
+        layer[xy(0, 0)] |= Char(b'@') + Fg(YELLOW) + Bg(RED);
+        layer[xy(3, 2)] |= Char(0xdb) + Fg(Color::rgba(1, 2, 3, 4));
+
+        let bytes = layer.serialize();
+        assert_eq!(&bytes[0..5], b"H437\x01");
+        assert_eq!(bytes.len(), 13 + 12 * 9);
+
+        let loaded = Layer::deserialize(&font, &bytes).unwrap();
+        assert_eq!(loaded.size(), layer.size());
+        assert!(loaded.iter().eq(layer.iter()));
+        assert_eq!(loaded.serialize(), bytes);
+    }
+
+    #[test]
+    fn test_deserialize_errors() {
+        let font = Font::default();
+        let bytes = Layer::new(&font, xy(2, 2), pxy(1, 1), pxy(0, 0)).serialize();
+
+        assert_eq!(Layer::deserialize(&font, b"nope").err(), Some(LayerFormatError::BadHeader));
+        let mut future = bytes.clone();
+        future[4] = 2;
+        assert_eq!(Layer::deserialize(&font, &future).err(), Some(LayerFormatError::UnsupportedVersion(2)));
+        assert_eq!(Layer::deserialize(&font, &bytes[0..20]).err(), Some(LayerFormatError::BadLength));
+        let mut empty = bytes.clone();
+        empty[5..9].copy_from_slice(&[0, 0, 0, 0]);
+        assert_eq!(Layer::deserialize(&font, &empty).err(), Some(LayerFormatError::BadSize));
+    }
+
+    #[test]
+    fn test_to_image() {
+        let font = Font::default();
+        let mut layer = Layer::new(&font, xy(2, 1), pxy(2, 1), pxy(30, 30));
+        layer[xy(1, 0)] |= Bg(RED);
+        let image = layer.to_image();
+        assert_eq!(image.dimensions(), (32, 8));
+        // The origin is ignored, so the second cell starts 16 pixels in:
+        assert_eq!(image.get_pixel(15, 0).0, [0, 0, 0, 0]);
+        assert_eq!(image.get_pixel(16, 0).0, [255, 0, 0, 255]);
+        assert_eq!(image.get_pixel(31, 7).0, [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_flip() {
+        let font = Font::default();
+        let mut layer = Layer::new(&font, xy(3, 2), pxy(1, 1), pxy(0, 0));
+        for (n, pt) in layer.size().into_iter().enumerate() { layer[pt] |= Char(b'a' + n as u8) }
+        layer.set_cursor(Some(xy(0, 0)));
+        let original = layer.clone();
+        let text = |layer: &Layer| layer.iter().map(|c| c.ch as char).collect::<String>();
+
+        layer.flip_h();
+        assert_eq!(text(&layer), "cbafed");
+        assert_eq!(layer.cursor(), Some(xy(2, 0)));
+        layer.flip_h();
+        assert_eq!(layer.data, original.data);
+        assert_eq!(layer.cursor(), Some(xy(0, 0)));
+
+        layer.flip_v();
+        assert_eq!(text(&layer), "defabc");
+        assert_eq!(layer.cursor(), Some(xy(0, 1)));
+        layer.flip_v();
+        assert_eq!(layer.data, original.data);
+        assert_eq!(layer.cursor(), Some(xy(0, 0)));
+    }
+
+    #[test]
+    fn test_get_text() {
+        let font = Font::default();
+        let mut layer = Layer::new(&font, xy(6, 3), pxy(1, 1), pxy(0, 0));
+        crate::Canvas::print(&mut layer, xy(1, 0), "ab d\nxy", None, None);
+        assert_eq!(layer.get_text(xy(0, 0), xy(6, 3)), " ab d\n xy\n");
+        assert_eq!(layer.get_text(xy(1, 0), xy(2, 2)), "ab\nxy");
+        // Off the edge reads as spaces:
+        assert_eq!(layer.get_text(xy(4, -1), xy(4, 2)), "\nd");
+        assert_eq!(layer.get_text(xy(0, 0), xy(0, 0)), "");
+    }
+
+    #[test]
+    fn test_clear_pixels_skipped() {
+        let font = Font::default();
+        let layer = Layer::new(&font, xy(1, 1), pxy(1, 1), pxy(0, 0));
+        let sprite = Sprite::from_char('@', WHITE, CLEAR, pxy(0, 0));
+        let behind = [10, 20, 30, 0];
+        let mut buf = behind.repeat(8 * 8);
+        layer.draw_sprites([&sprite], &mut buf, 8);
+
+        let glyph = font[b'@'];
+        for (px, pixel) in buf.chunks(4).enumerate() {
+            let (x, y) = (px % 8, px / 8);
+            if glyph.get_pixel(x, y) {
+                assert_eq!(pixel, &[255, 255, 255, 255]);
+            } else {
+                assert_eq!(pixel, &behind);
+            }
+        }
+    }
+
+    #[test]
+    fn test_draw_matches_blit() {
+        let font = Font::default();
+        let mut layer = Layer::new(&font, xy(12, 9), pxy(2, 3), pxy(-5, 7));
+        for (n, pt) in layer.size().into_iter().enumerate() {
+            let bg = if n % 3 == 0 { CLEAR } else { Color::rgba(0, n as u8, 100, 200) };
+            layer[pt] = Fg(WHITE) + Bg(bg) + Char(b'!' + (n % 90) as u8);
+        }
+        layer.opacity = 0.75;
+
+        // However `draw_shaken` splits up the work, it matches drawing each cell in turn:
+        let mut expected = vec![0u8; 100 * 150 * 4];
+        for (glyph, fg, bg, pc) in layer.cells() {
+            layer.blit(&mut expected, 100, glyph, fg, bg, pc + pxy(3, -4), pxy(2, 3))
+        }
+        let mut buf = vec![0u8; 100 * 150 * 4];
+        layer.draw_shaken(&mut buf, 100, pxy(3, -4));
+        assert_eq!(buf, expected);
+    }
+
+    #[test]
+    fn test_draw_clipped() {
+        let font = Font::default();
+        let mut layer = Layer::new(&font, xy(4, 4), pxy(1, 1), pxy(-4, 0));
+        for pt in layer.size() { layer[pt] |= Bg(RED) }
+        let mut buf = [0u8; 16 * 16 * 4];
+        layer.draw_clipped(&mut buf, 16, pxy(2, 3), pxy(5, 20));
+
+        for (n, pixel) in buf.chunks(4).enumerate() {
+            let (x, y) = (n % 16, n / 16);
+            let inside = (2..7).contains(&x) && (3..16).contains(&y);
+            assert_eq!(pixel, if inside { [255, 0, 0, 255] } else { [0; 4] }, "pixel {}, {}", x, y);
+        }
+    }
+}
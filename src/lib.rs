@@ -8,24 +8,33 @@ mod vecgrid;
 mod coords;
 mod keyboard;
 mod sprite;
+pub mod transition;
 
-pub use font::{ Font, Glyph };
-pub use color::{ Color, CLEAR, WHITE, BLACK, RED, GREEN, BLUE, YELLOW, PURPLE };
-pub use cell::{ Cell, Fg, Bg, Char, FgBg, FgChar, BgChar };
-pub use layer::{ Layer };
+pub use font::{ Font, FontError, Glyph, Codepage };
+pub use color::{ Color, ColorParseError, BlendMode, CLEAR, WHITE, BLACK, RED, GREEN, BLUE, YELLOW, PURPLE };
+pub use cell::{ Cell, Fg, Bg, Char, Blink, Style, FgBg, FgChar, BgChar, OptionCell };
+pub use layer::{ Layer, LayerFormatError };
 pub use sprite::Sprite;
-pub use drawing::{ Canvas, RectStyle, Wall };
+pub use drawing::{ Canvas, Align, RectStyle, Wall };
 pub use coords::{ Coord, xy, PixelCoord, pxy, Dir };
-pub use grid::{ Grid, GridMut, CountableNeighbors };
+pub use grid::{ Grid, GridMut, CountableNeighbors, SubGrid, SubGridMut, label_components };
 pub use vecgrid::{VecGrid};
 pub use keyboard::ToDirection;
+pub use transition::wipe;
 
 #[cfg(feature="rand")]
 mod mapgen;
 #[cfg(feature="rand")]
 pub use mapgen::CellularMap;
+#[cfg(feature="rand")]
+pub use transition::dissolve;
 
 #[cfg(feature = "fov")]
 mod fov;
 #[cfg(feature = "fov")]
-pub use fov::shadowcast;
+pub use fov::{ shadowcast, shadowcast_with_opts, symmetric_shadowcast, line_of_sight, FovOpts };
+
+#[cfg(feature = "path")]
+mod path;
+#[cfg(feature = "path")]
+pub use path::{ astar, astar_with_opts, PathOpts, dijkstra_map, downhill };
@@ -1,9 +1,8 @@
-use std::collections::HashSet;
 use std::ops::Range;
 use line_drawing::WalkGrid;
 use rand::prelude::{StdRng};
 use rand::Rng;
-use crate::{Coord, Grid, VecGrid, CountableNeighbors, xy};
+use crate::{Coord, Grid, VecGrid, CountableNeighbors, label_components, xy};
 
 pub struct CellularMap {
     size: Coord,
@@ -58,51 +57,42 @@ impl CellularMap {
 
     /// Build a cellular-automata random map
     pub fn build(self, rand: &mut StdRng) -> VecGrid<bool> {
-        let mut grid = VecGrid::new(self.size, true);
-
-        for pt in grid.size() {
-            grid[pt] = rand.gen_ratio((self.probability * 1000.0) as u32, 1000u32);
-        }
+        let mut grid = self.initial(rand);
 
         for _ in 0..self.generations {
-            let old = grid.clone();
-            for pt in old.size() {
-                let nbrs = (old.neighbors_equal(pt, true).count() +
-                    old.diagonals_equal(pt, true).count()) as i32;
-                if !old[pt] && self.born.contains(&nbrs) {
-                    grid[pt] = true // Born!
-                } else if old[pt] && !self.survive.contains(&nbrs) {
-                    grid[pt] = false // Dies.
-                }
-            }
+            self.step(&mut grid)
         }
 
         if self.connect { grid = connect_groups(grid) }
 
         grid
     }
-}
 
-fn bft<T, F: Fn(&T) -> bool>(grid: &impl Grid<CellType=T>, start: Coord, traversable: F) -> Vec<Coord> {
-    let mut open = vec![start];
-    let mut visited: Vec<Coord> = vec![];
-    let mut closed: HashSet<Coord> = HashSet::new();
-
-    while !open.is_empty() {
-        let curr = open.remove(0);
-        closed.insert(curr);
-        if traversable(grid.get(curr).unwrap()) {
-            visited.push(curr);
-            let mut to_add= vec![];
-            for nbr in grid.neighbor_coords(curr).filter(|c| !closed.contains(c) && !open.contains(c) && !visited.contains(c)) {
-
-                to_add.push(nbr)
+    /// Run a single generation of the automata on a grid. `build` does this `generations` times,
+    /// but calling it directly lets you animate a cave forming one generation at a time.
+    pub fn step(&self, grid: &mut VecGrid<bool>) {
+        let old = grid.clone();
+        for pt in old.size() {
+            let nbrs = (old.neighbors_equal(pt, true).count() +
+                old.diagonals_equal(pt, true).count()) as i32;
+            if !old[pt] && self.born.contains(&nbrs) {
+                grid[pt] = true // Born!
+            } else if old[pt] && !self.survive.contains(&nbrs) {
+                grid[pt] = false // Dies.
             }
-            open.append(&mut to_add);
         }
     }
 
-    visited
+    /// The random starting grid, before any generations are run
+    fn initial(&self, rand: &mut StdRng) -> VecGrid<bool> {
+        let mut grid = VecGrid::new(self.size, true);
+
+        for pt in grid.size() {
+            grid[pt] = rand.gen_ratio((self.probability * 1000.0) as u32, 1000u32);
+        }
+
+        grid
+    }
 }
 
 fn closest_between(group1: &Vec<Coord>, group2: &Vec<Coord>) -> (Coord, Coord, i32) {
@@ -142,30 +132,14 @@ fn shortest_tunnel(groups: &Vec<Vec<Coord>>) -> (usize, Coord, usize, Coord) {
 }
 
 fn connect_groups(grid: VecGrid<bool>) -> VecGrid<bool> {
-    // 0: wall; -1: unassigned group; 1+: some group
-    let mut group_num_grid: VecGrid<i32> = VecGrid::new(grid.size(), 0);
-
-    // First, replace all the empty spaces with -1, signifying unassigned:
-    for c in group_num_grid.size() {
-        if !grid[c] { group_num_grid[c] = -1 }
-    }
-
-    let mut group_num = 1;
+    // 0: wall; 1+: some group
+    let (mut group_num_grid, _) = label_components(&grid, |wall| !*wall);
     let mut groups: Vec<Vec<Coord>> = vec![];
-
-    loop {
-        // First, find some unassigned cell:
-        if let Some(start) = group_num_grid.find(|c| *c == -1) {
-            // Now, fill all the things that it's connected to:
-            let group_coords = bft(&group_num_grid, start, |c| *c == -1);
-            for g in group_coords.iter() {
-                group_num_grid[*g] = group_num
-            }
-            groups.push(group_coords);
-            group_num += 1;
-        } else {
-            break // All cells assigned, we're done!
-        }
+    for c in group_num_grid.size() {
+        let group_num = group_num_grid[c] as usize;
+        if group_num == 0 { continue }
+        if group_num > groups.len() { groups.push(vec![]) }
+        groups[group_num - 1].push(c)
     }
 
     // While more than one group remains:
@@ -201,16 +175,19 @@ fn connect_groups(grid: VecGrid<bool>) -> VecGrid<bool> {
 
 #[cfg(test)]
 mod test {
+    use rand::SeedableRng;
     use crate::xy;
     use super::*;
 
     #[test]
-    fn test_bft() {
-        let grid = VecGrid::from("....\n.++.\n.+..");
-        let cs = bft(&grid, xy(1, 1), |ch| *ch == '+');
-        assert!(cs.contains(&xy(1, 1)));
-        assert!(cs.contains(&xy(2, 1)));
-        assert!(cs.contains(&xy(1, 2)));
-        assert_eq!(cs.len(), 3);
+    fn test_step() {
+        let map = CellularMap::new(xy(20, 20)).with_connect(false);
+        let built = CellularMap::new(xy(20, 20)).with_connect(false).build(&mut StdRng::seed_from_u64(1));
+
+        let mut grid = map.initial(&mut StdRng::seed_from_u64(1));
+        for _ in 0..map.generations {
+            map.step(&mut grid)
+        }
+        assert_eq!(grid.iter().collect::<Vec<_>>(), built.iter().collect::<Vec<_>>());
     }
 }
\ No newline at end of file
@@ -0,0 +1,249 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+use crate::{Coord, Dir, Grid, VecGrid, xy};
+
+/// Find the shortest path between two cells with A*, stepping between orthogonal neighbors and
+/// only through cells that `passable` accepts. The path includes both `start` and `goal`; it's
+/// `None` if there's no way through, or either end is outside the grid. The start cell doesn't
+/// have to be passable itself (it's usually where the monster is standing), but the goal does.
+/// ```
+/// # use heart437::*;
+/// let map = VecGrid::from("..#..\n..#..\n.....");
+/// let path = astar(&map, xy(0, 0), xy(4, 0), |c| *c == '.').unwrap();
+/// assert_eq!(path.len(), 9);
+/// assert_eq!(path[4], xy(2, 2));
+/// ```
+pub fn astar<G: Grid>(grid: &G, start: Coord, goal: Coord, passable: impl Fn(&G::CellType) -> bool) -> Option<Vec<Coord>> {
+    astar_with_opts(grid, start, goal, PathOpts::new(), |_, cell| passable(cell).then_some(1))
+}
+
+/// Options for `astar_with_opts`, built like a `FovOpts`:
+/// ```
+/// # use heart437::*;
+/// let opts = PathOpts::new().with_diagonals(true);
+/// ```
+#[derive(Copy, Clone, Debug, Default)]
+pub struct PathOpts {
+    diagonals: bool
+}
+
+impl PathOpts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether paths can also step diagonally, at the same cost as an orthogonal step. Diagonal
+    /// steps can squeeze between two walls that touch at the corners. Defaults to false.
+    pub fn with_diagonals(mut self, diagonals: bool) -> Self {
+        self.diagonals = diagonals;
+        self
+    }
+}
+
+/// Just like `astar`, but with some extra options (see `PathOpts`), and a cost for entering each
+/// cell instead of just whether it's passable: `cost` gets the coord and contents of a cell and
+/// returns what it costs to step into it, or `None` if it can't be entered. Costs below 1 count
+/// as 1, so the path found is always the cheapest one. Handy for making monsters prefer roads,
+/// or avoid (but not refuse to cross) lava.
+/// ```
+/// # use heart437::*;
+/// let map = VecGrid::from("....\n.~~.\n....");
+/// let wade = |_: Coord, c: &char| Some(if *c == '~' { 5 } else { 1 });
+/// let path = astar_with_opts(&map, xy(0, 1), xy(3, 1), PathOpts::new(), wade).unwrap();
+/// assert!(path.iter().all(|pt| map[*pt] == '.'));
+/// ```
+pub fn astar_with_opts<G: Grid>(grid: &G, start: Coord, goal: Coord, opts: PathOpts, cost: impl Fn(Coord, &G::CellType) -> Option<u32>) -> Option<Vec<Coord>> {
+    if !grid.contains(start) || !grid.contains(goal) { return None }
+    let estimate = |pt: Coord| if opts.diagonals {
+        (pt.0 - goal.0).abs().max((pt.1 - goal.1).abs()) as u32
+    } else {
+        pt.manhattan_dist_to(goal) as u32
+    };
+
+    let mut spent = VecGrid::new(grid.size(), u32::MAX);
+    let mut came_from = VecGrid::new(grid.size(), None);
+    // Ordered by estimated total cost, then by the estimate alone, to prefer cells near the goal
+    let mut open = BinaryHeap::new();
+    spent[start] = 0;
+    open.push(Reverse((estimate(start), estimate(start), start.0, start.1)));
+
+    while let Some(Reverse((_, _, x, y))) = open.pop() {
+        let curr = xy(x, y);
+        if curr == goal {
+            let mut path = vec![goal];
+            while let Some(prev) = came_from[*path.last().unwrap()] { path.push(prev) }
+            path.reverse();
+            return Some(path)
+        }
+
+        for next in steps(grid, curr, opts.diagonals) {
+            let Some(step) = cost(next, grid.get(next).unwrap()) else { continue };
+            let total = spent[curr].saturating_add(step.max(1));
+            if total < spent[next] {
+                spent[next] = total;
+                came_from[next] = Some(curr);
+                open.push(Reverse((total.saturating_add(estimate(next)), estimate(next), next.0, next.1)))
+            }
+        }
+    }
+    None
+}
+
+/// A "Dijkstra map": how many orthogonal steps each cell is from the nearest of some sources,
+/// moving only through cells that `passable` accepts. Sources are 0 whether they're passable or
+/// not, cells that can't reach any source are `i32::MAX`, and sources outside the grid are
+/// ignored. Monsters chasing the player (the source) just walk downhill, see `downhill`; to flee,
+/// walk uphill instead.
+/// ```
+/// # use heart437::*;
+/// let map = VecGrid::from("...#.\n.#...\n.....");
+/// # let player = xy(0, 0);
+/// let distance = dijkstra_map(&map, &[player], |c| *c == '.');
+/// assert_eq!(distance[xy(4, 0)], 6);
+/// assert_eq!(distance[xy(3, 0)], i32::MAX);
+/// ```
+pub fn dijkstra_map<G: Grid>(grid: &G, sources: &[Coord], passable: impl Fn(&G::CellType) -> bool) -> VecGrid<i32> {
+    let mut distance = VecGrid::new(grid.size(), i32::MAX);
+    let mut open = VecDeque::new();
+    for &source in sources.iter().filter(|pt| grid.contains(**pt)) {
+        distance[source] = 0;
+        open.push_back(source)
+    }
+
+    // Breadth-first, so each cell is reached first by (one of) its shortest paths
+    while let Some(curr) = open.pop_front() {
+        let next: Vec<Coord> = grid.neighbor_coords(curr)
+            .filter(|n| distance[*n] == i32::MAX && passable(grid.get(*n).unwrap()))
+            .collect();
+        for n in next {
+            distance[n] = distance[curr] + 1;
+            open.push_back(n)
+        }
+    }
+    distance
+}
+
+/// Which way is downhill from each cell of a `dijkstra_map`: the direction of the orthogonal
+/// neighbor with the lowest distance, if it's lower than the cell's own. Sources and unreachable
+/// cells are `None`. Ties go to the first of north, east, south, and west.
+/// ```
+/// # use heart437::*;
+/// let map = VecGrid::new(xy(5, 5), true);
+/// let downhill = downhill(&dijkstra_map(&map, &[xy(2, 2)], |open| *open));
+/// assert!(downhill[xy(2, 0)] == Some(Dir::South));
+/// ```
+pub fn downhill(distance: &VecGrid<i32>) -> VecGrid<Option<Dir>> {
+    distance.map_grid(|pt, here| {
+        if *here == i32::MAX { return None }
+        let mut best = (*here, None);
+        for dir in [Dir::North, Dir::East, Dir::South, Dir::West] {
+            if let Some(&there) = distance.get(pt.translate(dir)) {
+                if there < best.0 { best = (there, Some(dir)) }
+            }
+        }
+        best.1
+    }, None)
+}
+
+/// The in-bounds cells one step away from a point, orthogonally and maybe diagonally
+fn steps<G: Grid>(grid: &G, pt: Coord, diagonals: bool) -> Vec<Coord> {
+    if diagonals {
+        grid.neighbor_coords8(pt).collect()
+    } else {
+        grid.neighbor_coords(pt).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const MAZE: &str = "\
+.#......
+.#.####.
+.#.#..#.
+...#.##.
+####....";
+
+    #[test]
+    fn test_astar() {
+        let map = VecGrid::from(MAZE);
+        let open = |c: &char| *c == '.';
+
+        let path = astar(&map, xy(0, 0), xy(4, 2), open).unwrap();
+        assert_eq!(path.len(), 23);
+        assert_eq!((path[0], path[22]), (xy(0, 0), xy(4, 2)));
+        for step in path.windows(2) {
+            assert!(step[0].adjacent(step[1]));
+            assert_eq!(map[step[1]], '.');
+        }
+
+        assert_eq!(astar(&map, xy(2, 2), xy(2, 2), open), Some(vec![xy(2, 2)]));
+        assert_eq!(astar(&map, xy(0, 0), xy(1, 0), open), None); // Goal's a wall
+        assert_eq!(astar(&map, xy(0, 0), xy(8, 0), open), None); // Off the map
+        let island = VecGrid::from(".#.\n##.");
+        assert_eq!(astar(&island, xy(0, 0), xy(2, 1), open), None);
+    }
+
+    #[test]
+    fn test_astar_with_opts() {
+        let map = VecGrid::from("...\n.#.\n...");
+        let open = |_: Coord, c: &char| (*c == '.').then_some(1);
+
+        let path = astar_with_opts(&map, xy(0, 0), xy(2, 2), PathOpts::new(), open).unwrap();
+        assert_eq!(path.len(), 5);
+        let path = astar_with_opts(&map, xy(0, 0), xy(2, 0), PathOpts::new().with_diagonals(true), open).unwrap();
+        assert_eq!(path, vec![xy(0, 0), xy(1, 0), xy(2, 0)]);
+        let path = astar_with_opts(&map, xy(0, 1), xy(2, 1), PathOpts::new().with_diagonals(true), open).unwrap();
+        assert_eq!(path.len(), 3);
+        assert!(path[1] == xy(1, 0) || path[1] == xy(1, 2));
+
+        // A cheaper detour wins over a shorter, expensive path:
+        let map = VecGrid::from(".....\n.~~~.\n.....");
+        let wade = |_: Coord, c: &char| Some(if *c == '~' { 2 } else { 1 });
+        let path = astar_with_opts(&map, xy(0, 1), xy(4, 1), PathOpts::new(), wade).unwrap();
+        assert_eq!(path.len(), 7);
+        // Costs below 1 count as 1, so this is just the shortest path:
+        let wade = |_: Coord, c: &char| Some(if *c == '~' { 0 } else { 1 });
+        let path = astar_with_opts(&map, xy(0, 1), xy(4, 1), PathOpts::new(), wade).unwrap();
+        assert_eq!(path.len(), 5);
+    }
+
+    #[test]
+    fn test_dijkstra_map() {
+        let map = VecGrid::from(MAZE);
+        let distance = dijkstra_map(&map, &[xy(0, 0), xy(7, 4)], |c| *c == '.');
+        assert_eq!(distance[xy(0, 0)], 0);
+        assert_eq!(distance[xy(2, 0)], 8);
+        assert_eq!(distance[xy(7, 0)], 4);
+        assert_eq!(distance[xy(4, 2)], 5);
+        assert_eq!(distance[xy(1, 0)], i32::MAX); // A wall
+        // Every reachable cell is one more than some neighbor:
+        for pt in map.size() {
+            let d = distance[pt];
+            if d == 0 || d == i32::MAX { continue }
+            assert!(map.neighbor_coords(pt).any(|n| distance[n] == d - 1));
+        }
+
+        let nowhere = dijkstra_map(&map, &[xy(-1, 0)], |c| *c == '.');
+        assert!(nowhere.iter().all(|d| *d == i32::MAX));
+    }
+
+    #[test]
+    fn test_downhill() {
+        let map = VecGrid::from(MAZE);
+        let distance = dijkstra_map(&map, &[xy(4, 2)], |c| *c == '.');
+        let downhill = downhill(&distance);
+        assert!(downhill[xy(4, 2)].is_none());
+        assert!(downhill[xy(1, 0)].is_none());
+
+        // Walking downhill from anywhere reachable gets to the source:
+        let mut pt = xy(0, 0);
+        let mut steps = 0;
+        while let Some(dir) = downhill[pt] {
+            pt = pt.translate(dir);
+            steps += 1;
+        }
+        assert_eq!((pt, steps), (xy(4, 2), 22));
+    }
+}
@@ -1,8 +1,53 @@
-use crate::{Cell, PixelCoord};
+use crate::{Bg, Cell, Char, Color, Fg, PixelCoord, pxy};
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Sprite {
     pub cell: Cell,
     pub position: PixelCoord,
     pub scale: PixelCoord,
+    /// Invisible sprites are skipped by `Layer::draw_sprites`
+    pub visible: bool,
+}
+
+impl Sprite {
+    /// Create a sprite showing a single char at a given position, with a 1x scale.
+    /// A sprite isn't tied to a font, so the char isn't looked up in a `Codepage`: its ASCII code
+    /// is used as the glyph's slot, and it will panic if passed a non-ASCII char! For anything
+    /// else, look the slot up with `Codepage::slot` and build the sprite's `cell` with `Char`.
+    /// ```
+    /// # use heart437::*;
+    /// let player = Sprite::from_char('@', WHITE, CLEAR, pxy(40, 40));
+    /// ```
+    pub fn from_char(ch: char, fg: Color, bg: Color, position: PixelCoord) -> Sprite {
+        assert!(ch.is_ascii(), "Sprite::from_char only takes ASCII chars!");
+        Sprite {
+            cell: Fg(fg) + Bg(bg) + Char(ch as u8),
+            position,
+            scale: pxy(1, 1),
+            visible: true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{BLUE, Style, YELLOW};
+    use super::*;
+
+    #[test]
+    fn test_from_char() {
+        let sprite = Sprite::from_char('@', YELLOW, BLUE, pxy(10, 20));
+        assert_eq!(sprite, Sprite {
+            cell: Cell { ch: b'@', fg: YELLOW, bg: BLUE, blink: false, style: Style::NONE },
+            position: pxy(10, 20),
+            scale: pxy(1, 1),
+            visible: true,
+        });
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_from_non_ascii() {
+        Sprite::from_char('☺', YELLOW, BLUE, pxy(0, 0));
+    }
 }
@@ -0,0 +1,132 @@
+//! Effects for switching from one layer to another, like a scene change. The functions here are
+//! also exported at the crate root.
+//! ```
+//! # use heart437::*;
+//! # let font = Font::default();
+//! # let title = Layer::new(&font, xy(80, 25), pxy(1, 1), pxy(0, 0));
+//! # let game = Layer::new(&font, xy(80, 25), pxy(1, 1), pxy(0, 0));
+//! let frame = heart437::transition::wipe(&title, &game, 0.5, Dir::South);
+//! ```
+
+use crate::{Coord, Dir, Grid, Layer};
+#[cfg(feature = "rand")]
+use rand::Rng;
+
+/// Produce one frame of a wipe from one layer to another: `to` sweeps across `from` in the given
+/// direction, covering `progress` (0..1) of it. For example, `Dir::East` starts at the left edge
+/// and wipes rightward, so at 0.5 the left half is `to` and the right half is still `from`.
+/// The result has the size, font, scale, and origin of `from`.
+/// ```
+/// # use heart437::*;
+/// # let font = Font::default();
+/// let title = Layer::new(&font, xy(80, 25), pxy(1, 1), pxy(0, 0));
+/// let game = Layer::new(&font, xy(80, 25), pxy(1, 1), pxy(0, 0));
+/// let frame = wipe(&title, &game, 0.25, Dir::East);
+/// ```
+pub fn wipe<'a>(from: &Layer<'a>, to: &Layer<'a>, progress: f32, dir: Dir) -> Layer<'a> {
+    let progress = progress.clamp(0.0, 1.0);
+    let Coord(width, height) = from.size();
+    let (covered_x, covered_y) = ((progress * width as f32) as i32, (progress * height as f32) as i32);
+    let covered = |pt: Coord| match dir {
+        Dir::East => pt.0 < covered_x,
+        Dir::West => pt.0 >= width - covered_x,
+        Dir::South => pt.1 < covered_y,
+        Dir::North => pt.1 >= height - covered_y,
+    };
+
+    let mut frame = from.clone();
+    for pt in frame.size() {
+        if covered(pt) {
+            if let Some(cell) = to.get(pt) { frame[pt] = *cell }
+        }
+    }
+    frame
+}
+
+/// Produce one frame of a dissolve from one layer to another: a random `progress` (0..1) fraction
+/// of the cells show `to`, and the rest still show `from`. At 1.0 the result equals `to`.
+/// Each cell gets a threshold from the rng, in reading order, and switches over once `progress`
+/// passes it. To keep the cells from flickering between frames, pass an identically-seeded rng
+/// every frame; then as `progress` increases, cells only ever switch from `from` to `to`.
+/// The result has the size, font, scale, and origin of `from`.
+/// ```
+/// # use heart437::*;
+/// # use rand::SeedableRng;
+/// # let font = Font::default();
+/// let title = Layer::new(&font, xy(80, 25), pxy(1, 1), pxy(0, 0));
+/// let game = Layer::new(&font, xy(80, 25), pxy(1, 1), pxy(0, 0));
+/// let mut rng = rand::rngs::StdRng::seed_from_u64(1234);
+/// let frame = dissolve(&title, &game, 0.25, &mut rng);
+/// ```
+#[cfg(feature = "rand")]
+pub fn dissolve<'a>(from: &Layer<'a>, to: &Layer<'a>, progress: f32, rng: &mut impl Rng) -> Layer<'a> {
+    let mut frame = from.clone();
+    for pt in frame.size() {
+        let threshold: f32 = rng.gen();
+        if threshold < progress {
+            if let Some(cell) = to.get(pt) { frame[pt] = *cell }
+        }
+    }
+    frame
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Bg, BLUE, Font, pxy, RED, xy};
+    use super::*;
+
+    fn layers(font: &Font) -> (Layer<'_>, Layer<'_>) {
+        let mut from = Layer::new(font, xy(4, 4), pxy(1, 1), pxy(0, 0));
+        let mut to = Layer::new(font, xy(4, 4), pxy(1, 1), pxy(0, 0));
+        for pt in from.size() {
+            from[pt] |= Bg(RED);
+            to[pt] |= Bg(BLUE);
+        }
+        (from, to)
+    }
+
+    #[test]
+    fn test_wipe() {
+        let font = Font::default();
+        let (from, to) = layers(&font);
+
+        let frame = wipe(&from, &to, 0.5, Dir::East);
+        for pt in frame.size() {
+            assert_eq!(frame[pt], if pt.0 < 2 { to[pt] } else { from[pt] });
+        }
+
+        let frame = wipe(&from, &to, 0.25, Dir::North);
+        for pt in frame.size() {
+            assert_eq!(frame[pt], if pt.1 == 3 { to[pt] } else { from[pt] });
+        }
+
+        // Progress is clamped:
+        let done = wipe(&from, &to, 1.5, Dir::West);
+        assert!(done.iter().all(|c| c.bg == BLUE));
+        let start = wipe(&from, &to, -1.0, Dir::South);
+        assert!(start.iter().all(|c| c.bg == RED));
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn test_dissolve() {
+        use rand::SeedableRng;
+        use rand::rngs::StdRng;
+        let font = Font::default();
+        let (from, to) = layers(&font);
+
+        let mut last = dissolve(&from, &to, 0.0, &mut StdRng::seed_from_u64(7));
+        assert!(last.iter().all(|c| c.bg == RED));
+        for progress in [0.25, 0.5, 0.75, 1.0] {
+            let frame = dissolve(&from, &to, progress, &mut StdRng::seed_from_u64(7));
+            // Every cell that had already dissolved still has:
+            for pt in frame.size() {
+                if last[pt].bg == BLUE { assert_eq!(frame[pt].bg, BLUE) }
+            }
+            let changed = |l: &Layer| l.iter().filter(|c| c.bg == BLUE).count();
+            assert!(changed(&frame) >= changed(&last));
+            last = frame;
+        }
+        assert!(last.iter().all(|c| c.bg == BLUE));
+    }
+}
\ No newline at end of file
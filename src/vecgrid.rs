@@ -66,6 +66,131 @@ impl<T: Clone> VecGrid<T> {
         let list = self.map(func);
         VecGrid::from_vec(list, self.width, default)
     }
+
+    /// A copy of the grid flipped across its main diagonal, so rows become columns: the cell at
+    /// (x, y) moves to (y, x), and the width and height swap.
+    /// ```
+    /// # use heart437::*;
+    /// let grid = VecGrid::from("ABC\nDEF").transpose();
+    /// assert_eq!(Into::<String>::into(grid), "AD\nBE\nCF");
+    /// ```
+    pub fn transpose(&self) -> VecGrid<T> {
+        self.remap(|Coord(x, y)| xy(y, x))
+    }
+
+    /// A copy of the grid rotated a quarter turn clockwise; the width and height swap.
+    /// ```
+    /// # use heart437::*;
+    /// let grid = VecGrid::from("ABC\nDEF").rotate_cw();
+    /// assert_eq!(Into::<String>::into(grid), "DA\nEB\nFC");
+    /// ```
+    pub fn rotate_cw(&self) -> VecGrid<T> {
+        let height = self.size().1;
+        self.remap(|Coord(x, y)| xy(y, height - 1 - x))
+    }
+
+    /// A copy of the grid rotated a quarter turn counterclockwise; the width and height swap.
+    pub fn rotate_ccw(&self) -> VecGrid<T> {
+        let width = self.size().0;
+        self.remap(|Coord(x, y)| xy(width - 1 - y, x))
+    }
+
+    /// A new grid with the width and height swapped, where each cell is copied from the cell of
+    /// this grid that `source` says it came from
+    fn remap<F: Fn(Coord) -> Coord>(&self, source: F) -> VecGrid<T> {
+        let Coord(width, height) = self.size();
+        let cells = xy(height, width).into_iter().map(|pt| self[source(pt)].clone()).collect();
+        VecGrid::from_vec(cells, height as usize, self.default.clone())
+    }
+}
+
+impl VecGrid<f32> {
+    /// Box-blur the grid: each cell becomes the average of all the cells within `radius` of it
+    /// (a square neighborhood). Near the edges only the cells actually in the grid are averaged,
+    /// so nothing wraps and values aren't dragged toward zero.
+    /// ```
+    /// # use heart437::*;
+    /// let mut costs = VecGrid::new(xy(5, 5), 0.0);
+    /// costs[xy(2, 2)] = 9.0;
+    /// let smooth = costs.blur(1);
+    /// assert_eq!(smooth[xy(1, 1)], 1.0);
+    /// ```
+    pub fn blur(&self, radius: i32) -> VecGrid<f32> {
+        let radius = radius.max(0);
+        self.map_grid(|pt, _| {
+            let (mut total, mut count) = (0.0, 0);
+            for y in (pt.1 - radius)..=(pt.1 + radius) {
+                for x in (pt.0 - radius)..=(pt.0 + radius) {
+                    if let Some(v) = self.get(xy(x, y)) {
+                        total += v;
+                        count += 1;
+                    }
+                }
+            }
+            total / count as f32
+        }, self.default)
+    }
+
+    /// Run one step of diffusion, for spreading gas, heat, and so on: each cell gives away `rate`
+    /// (0..1) of its value, split evenly between its orthogonal neighbors that aren't blocked.
+    /// Blocked cells neither give nor receive anything, and a cell with nowhere to spread keeps its
+    /// value, so the total is conserved.
+    /// ```
+    /// # use heart437::*;
+    /// let mut gas = VecGrid::new(xy(10, 10), 0.0);
+    /// gas[xy(5, 5)] = 100.0;
+    /// let walls = VecGrid::new(xy(10, 10), false);
+    /// for _ in 0..10 { gas = gas.diffuse_step(0.5, &walls) }
+    /// ```
+    pub fn diffuse_step(&self, rate: f32, blocked: &VecGrid<bool>) -> VecGrid<f32> {
+        let rate = rate.clamp(0.0, 1.0);
+        let is_blocked = |pt: Coord| *blocked.get(pt).unwrap_or(&false);
+        let mut next = self.clone();
+
+        for pt in self.size() {
+            if is_blocked(pt) { continue }
+            let open: Vec<Coord> = self.neighbor_coords(pt).filter(|n| !is_blocked(*n)).collect();
+            if open.is_empty() { continue }
+
+            let share = self[pt] * rate / open.len() as f32;
+            next[pt] -= self[pt] * rate;
+            for n in open { next[n] += share }
+        }
+        next
+    }
+
+    /// Add two grids cell-by-cell. The grids must be the same size; will panic otherwise!
+    pub fn add(&self, other: &VecGrid<f32>) -> VecGrid<f32> {
+        self.zip_with(other, |a, b| a + b)
+    }
+
+    /// Subtract another grid from this one cell-by-cell. The grids must be the same size; will
+    /// panic otherwise!
+    pub fn sub(&self, other: &VecGrid<f32>) -> VecGrid<f32> {
+        self.zip_with(other, |a, b| a - b)
+    }
+
+    /// Multiply every cell by a constant
+    pub fn scale(&self, factor: f32) -> VecGrid<f32> {
+        self.map_grid(|_, v| v * factor, self.default)
+    }
+
+    /// Take the larger of the two grids' values in each cell. The grids must be the same size;
+    /// will panic otherwise!
+    /// ```
+    /// # use heart437::*;
+    /// let threat = VecGrid::from_vec(vec![1.0, 5.0], 2, 0.0);
+    /// let safety = VecGrid::from_vec(vec![3.0, 2.0], 2, 0.0);
+    /// assert_eq!(threat.max_with(&safety).iter().collect::<Vec<_>>(), vec![&3.0, &5.0]);
+    /// ```
+    pub fn max_with(&self, other: &VecGrid<f32>) -> VecGrid<f32> {
+        self.zip_with(other, f32::max)
+    }
+
+    fn zip_with<F: Fn(f32, f32) -> f32>(&self, other: &VecGrid<f32>, func: F) -> VecGrid<f32> {
+        assert_eq!(self.size(), other.size(), "Grids must be the same size!");
+        self.map_grid(|pt, v| func(*v, other[pt]), self.default)
+    }
 }
 
 impl From<&str> for VecGrid<char> {
@@ -104,4 +229,78 @@ mod test {
         assert_eq!(grid[xy(0, 1)], 'C');
         assert_eq!(grid.get(xy(2, 2)), None);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_blur() {
+        let mut grid = VecGrid::new(xy(5, 5), 0.0);
+        grid[xy(2, 2)] = 9.0;
+        let blurred = grid.blur(1);
+
+        // The spike spreads evenly into its neighbors, and no further:
+        for pt in blurred.size() {
+            let expected = if pt.0 >= 1 && pt.0 <= 3 && pt.1 >= 1 && pt.1 <= 3 { 1.0 } else { 0.0 };
+            assert_eq!(blurred[pt], expected);
+        }
+        let total: f32 = blurred.iter().sum();
+        assert!((total - 9.0).abs() < 0.001);
+
+        // Edges only average the cells that exist:
+        let edge = VecGrid::new(xy(3, 3), 2.0).blur(1);
+        assert_eq!(edge[xy(0, 0)], 2.0);
+    }
+
+    #[test]
+    fn test_diffuse_step() {
+        let mut walls = VecGrid::new(xy(5, 5), false);
+        walls[xy(3, 2)] = true;
+        let mut gas = VecGrid::new(xy(5, 5), 0.0);
+        gas[xy(2, 2)] = 100.0;
+
+        gas = gas.diffuse_step(0.4, &walls);
+        assert!((gas[xy(2, 2)] - 60.0).abs() < 0.001);
+        assert!((gas[xy(2, 1)] - 40.0 / 3.0).abs() < 0.001);
+        assert_eq!(gas[xy(3, 2)], 0.0);
+
+        for _ in 0..10 { gas = gas.diffuse_step(0.4, &walls) }
+        assert!(gas[xy(0, 0)] > 0.0);
+        assert_eq!(gas[xy(3, 2)], 0.0);
+        let total: f32 = gas.iter().sum();
+        assert!((total - 100.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let a = VecGrid::from_vec(vec![1.0, 2.0, 3.0, 4.0], 2, 0.0);
+        let b = VecGrid::from_vec(vec![4.0, 3.0, 2.0, 1.0], 2, 0.0);
+        let values = |g: VecGrid<f32>| g.iter().cloned().collect::<Vec<_>>();
+
+        assert_eq!(values(a.add(&b)), vec![5.0, 5.0, 5.0, 5.0]);
+        assert_eq!(values(a.sub(&b)), vec![-3.0, -1.0, 1.0, 3.0]);
+        assert_eq!(values(a.scale(0.5)), vec![0.5, 1.0, 1.5, 2.0]);
+        assert_eq!(values(a.max_with(&b)), vec![4.0, 3.0, 3.0, 4.0]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_arithmetic_mismatch() {
+        let a = VecGrid::new(xy(2, 2), 0.0);
+        let b = VecGrid::new(xy(4, 1), 0.0);
+        a.add(&b);
+    }
+
+    #[test]
+    fn test_transforms() {
+        let grid = VecGrid::from("ABCD\nEFGH\nIJKL");
+        let text = |g: VecGrid<char>| Into::<String>::into(g);
+
+        assert_eq!(grid.transpose().size(), xy(3, 4));
+        assert_eq!(text(grid.transpose()), "AEI\nBFJ\nCGK\nDHL");
+        assert_eq!(text(grid.rotate_cw()), "IEA\nJFB\nKGC\nLHD");
+        assert_eq!(text(grid.rotate_ccw()), "DHL\nCGK\nBFJ\nAEI");
+
+        assert_eq!(text(grid.transpose().transpose()), text(grid.clone()));
+        assert_eq!(text(grid.rotate_cw().rotate_ccw()), text(grid.clone()));
+        assert_eq!(text(grid.rotate_cw().rotate_cw()), "LKJI\nHGFE\nDCBA");
+        assert_eq!(grid.rotate_cw().default(), ' ');
+    }
+}